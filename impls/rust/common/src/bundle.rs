@@ -0,0 +1,201 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::parser::{self, errors::ReplError};
+
+// An executable carries its bundle after the interpreter itself:
+// [interpreter][entries][payload length: u64][MAGIC]
+const MAGIC: &[u8; 8] = b"MALBUNDL";
+const TRAILER_LEN: u64 = 16;
+
+pub struct Bundle {
+    pub main: Box<str>,
+    pub files: Vec<(Box<str>, Box<str>)>,
+}
+
+impl Bundle {
+    pub fn collect(script: &str) -> Result<Bundle, ReplError> {
+        let mut files: Vec<(Box<str>, Box<str>)> = Vec::new();
+        let mut pending: Vec<Box<str>> = vec![script.into()];
+
+        while let Some(path) = pending.pop() {
+            if files.iter().any(|(loaded, _)| *loaded == path) {
+                continue;
+            }
+            let contents = fs::read_to_string(path.as_ref())?;
+            pending.extend(parser::loaded_files(&contents)?);
+            files.push((path, contents.into()));
+        }
+
+        Ok(Bundle {
+            main: script.into(),
+            files,
+        })
+    }
+
+    // An executable that cannot be opened or read, e.g. one installed execute-only, is treated
+    // as having no bundle. Once the trailer is found, anything wrong with it is an error.
+    pub fn embedded() -> io::Result<Option<Bundle>> {
+        match env::current_exe().and_then(File::open) {
+            Ok(mut exe) => Bundle::read(&mut exe),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn read(exe: &mut (impl Read + Seek)) -> io::Result<Option<Bundle>> {
+        let Ok(Some((len, payload_len))) = read_trailer(exe) else {
+            return Ok(None);
+        };
+
+        let Some(start) = (len - TRAILER_LEN).checked_sub(payload_len) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "bundle is longer than the executable",
+            ));
+        };
+        let mut payload = vec![0u8; payload_len as usize];
+        exe.seek(SeekFrom::Start(start))?;
+        exe.read_exact(&mut payload)?;
+
+        Bundle::decode(&payload).map(Some)
+    }
+
+    pub fn write(&self, output: &Path) -> io::Result<()> {
+        fs::copy(env::current_exe()?, output)?;
+        self.append(&mut OpenOptions::new().append(true).open(output)?)
+    }
+
+    fn append(&self, out: &mut impl Write) -> io::Result<()> {
+        let payload = self.encode();
+        out.write_all(&payload)?;
+        out.write_all(&(payload.len() as u64).to_le_bytes())?;
+        out.write_all(MAGIC)
+    }
+
+    pub fn register(&self) {
+        for (path, contents) in &self.files {
+            parser::register_file(path, contents);
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_str(&mut out, &self.main);
+        out.extend((self.files.len() as u64).to_le_bytes());
+        for (path, contents) in &self.files {
+            write_str(&mut out, path);
+            write_str(&mut out, contents);
+        }
+        out
+    }
+
+    fn decode(mut payload: &[u8]) -> io::Result<Bundle> {
+        let main = read_str(&mut payload)?;
+        let count = read_u64(&mut payload)?;
+        let mut files = Vec::new();
+        for _ in 0..count {
+            files.push((read_str(&mut payload)?, read_str(&mut payload)?));
+        }
+        Ok(Bundle { main, files })
+    }
+}
+
+// Returns the length of the executable and of its bundle, if it ends with a trailer.
+fn read_trailer(exe: &mut (impl Read + Seek)) -> io::Result<Option<(u64, u64)>> {
+    let len = exe.seek(SeekFrom::End(0))?;
+    if len < TRAILER_LEN {
+        return Ok(None);
+    }
+
+    let mut trailer = [0u8; TRAILER_LEN as usize];
+    exe.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    exe.read_exact(&mut trailer)?;
+    if &trailer[8..] != MAGIC {
+        return Ok(None);
+    }
+    let payload_len = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+    Ok(Some((len, payload_len)))
+}
+
+fn write_str(out: &mut Vec<u8>, str: &str) {
+    out.extend((str.len() as u64).to_le_bytes());
+    out.extend(str.as_bytes());
+}
+
+fn read_u64(payload: &mut &[u8]) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    payload.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_str(payload: &mut &[u8]) -> io::Result<Box<str>> {
+    let len = read_u64(payload)? as usize;
+    if len > payload.len() {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let (str, rest) = payload.split_at(len);
+    *payload = rest;
+    String::from_utf8(str.to_vec())
+        .map(Into::into)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn bundle() -> Bundle {
+        Bundle {
+            main: "main.mal".into(),
+            files: vec![
+                ("main.mal".into(), "(load-file \"lib.mal\")".into()),
+                ("lib.mal".into(), "(def! x \"é\")".into()),
+            ],
+        }
+    }
+
+    fn executable(bundle: &Bundle) -> Vec<u8> {
+        let mut exe = b"\x7fELF interpreter".to_vec();
+        bundle.append(&mut exe).unwrap();
+        exe
+    }
+
+    #[test]
+    fn reads_back_the_bundle_it_appends() {
+        let read = Bundle::read(&mut Cursor::new(executable(&bundle())))
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.main, bundle().main);
+        assert_eq!(read.files, bundle().files);
+    }
+
+    #[test]
+    fn finds_no_bundle_without_a_trailer() {
+        for exe in [
+            &b""[..],
+            b"short",
+            b"\x7fELF an interpreter without a bundle",
+        ] {
+            assert!(Bundle::read(&mut Cursor::new(exe)).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_bundles() {
+        // The trailer survives but the start of the file was cut off.
+        let exe = executable(&bundle());
+        let cut = exe[exe.len() - TRAILER_LEN as usize - 10..].to_vec();
+        assert!(Bundle::read(&mut Cursor::new(cut)).is_err());
+
+        // The payload the trailer describes ends in the middle of a file.
+        let payload = bundle().encode();
+        let mut cut = b"\x7fELF interpreter".to_vec();
+        cut.extend(&payload[..payload.len() - 4]);
+        cut.extend((payload.len() as u64 - 4).to_le_bytes());
+        cut.extend(MAGIC);
+        assert!(Bundle::read(&mut Cursor::new(cut)).is_err());
+    }
+}
@@ -1 +1,3 @@
+pub mod bundle;
 pub mod parser;
+pub mod repl;
@@ -0,0 +1,442 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use regex::Regex;
+
+use crate::parser::errors::ReplError;
+use crate::parser::reader;
+use crate::parser::types::{self, MalType, Tokens};
+
+// [MAGIC][VERSION][string count][strings...][value]
+//
+// Strings, symbols and regex patterns are stored once in the string table and referenced by
+// index. Atoms are numbered in the order they are first written, so an atom reached again,
+// including through itself, is written as a reference to that number. Non-empty collections are
+// numbered in the order they are finished, and one identical to a collection already written is
+// written as a reference to it.
+const MAGIC: &[u8; 4] = b"MALB";
+const VERSION: u8 = 1;
+
+const NIL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const NUMBER: u8 = 3;
+const FLOAT: u8 = 4;
+const STRING: u8 = 5;
+const SYMBOL: u8 = 6;
+const LIST: u8 = 7;
+const VECTOR: u8 = 8;
+const HASHMAP: u8 = 9;
+const SET: u8 = 10;
+const REGEX: u8 = 11;
+const ATOM: u8 = 12;
+const ATOM_REF: u8 = 13;
+const COLLECTION_REF: u8 = 14;
+
+pub fn serialize(value: &MalType) -> Result<Vec<u8>, ReplError> {
+    let mut encoder = Encoder::default();
+    encoder.value(value)?;
+
+    let mut out = Vec::with_capacity(encoder.body.len() + 16);
+    out.extend(MAGIC);
+    out.push(VERSION);
+    write_varint(&mut out, encoder.strings.len() as u128);
+    for string in &encoder.strings {
+        write_varint(&mut out, string.len() as u128);
+        out.extend(string.as_bytes());
+    }
+    out.extend(encoder.body);
+    Ok(out)
+}
+
+pub fn deserialize(bytes: &[u8]) -> Result<MalType, ReplError> {
+    let rest = bytes
+        .strip_prefix(MAGIC)
+        .ok_or_else(|| invalid("missing header"))?;
+    let (&version, rest) = rest
+        .split_first()
+        .ok_or_else(|| invalid("missing version"))?;
+    if version != VERSION {
+        return Err(invalid(&format!("unsupported version {version}")));
+    }
+
+    let mut decoder = Decoder {
+        bytes: rest,
+        depth: 0,
+        strings: Vec::new(),
+        atoms: Vec::new(),
+        collections: Vec::new(),
+    };
+    let count = decoder.len()?;
+    for _ in 0..count {
+        let len = decoder.len()?;
+        let string = std::str::from_utf8(decoder.take(len)?)
+            .map_err(|_| invalid("string is not valid UTF-8"))?;
+        decoder.strings.push(string.into());
+    }
+
+    let value = decoder.value()?;
+    if !decoder.bytes.is_empty() {
+        return Err(invalid("trailing bytes after value"));
+    }
+    Ok(value)
+}
+
+fn invalid(message: &str) -> ReplError {
+    ReplError::Conversion(format!("invalid binary data, {message}").into())
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+#[derive(Default)]
+struct Encoder {
+    body: Vec<u8>,
+    strings: Vec<Box<str>>,
+    string_ids: HashMap<Box<str>, usize>,
+    atom_ids: HashMap<*const RefCell<MalType>, usize>,
+    collection_ids: HashMap<MalType, usize>,
+    collection_count: usize,
+}
+
+impl Encoder {
+    fn string(&mut self, tag: u8, string: &str) {
+        let id = match self.string_ids.get(string) {
+            Some(&id) => id,
+            None => {
+                let id = self.strings.len();
+                self.strings.push(string.into());
+                self.string_ids.insert(string.into(), id);
+                id
+            }
+        };
+        self.body.push(tag);
+        write_varint(&mut self.body, id as u128);
+    }
+
+    fn collection<'a>(
+        &mut self,
+        collection: &MalType,
+        tag: u8,
+        len: usize,
+        mut elements: impl Iterator<Item = &'a MalType>,
+    ) -> Result<(), ReplError> {
+        if let Some(&id) = self.collection_ids.get(collection) {
+            self.body.push(COLLECTION_REF);
+            write_varint(&mut self.body, id as u128);
+            return Ok(());
+        }
+
+        self.body.push(tag);
+        write_varint(&mut self.body, len as u128);
+        elements.try_for_each(|element| self.value(element))?;
+        // A collection reached again through an atom inside it is finished more than once. Each
+        // time still takes a number, as the decoder counts it, but references use the first.
+        if len > 0 {
+            self.collection_ids
+                .entry(collection.clone())
+                .or_insert(self.collection_count);
+            self.collection_count += 1;
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, value: &MalType) -> Result<(), ReplError> {
+        match value {
+            MalType::Nil => self.body.push(NIL),
+            MalType::Bool(false) => self.body.push(FALSE),
+            MalType::Bool(true) => self.body.push(TRUE),
+            MalType::Number(number) => {
+                self.body.push(NUMBER);
+                // Zigzag encoding keeps small negative numbers short.
+                write_varint(&mut self.body, ((number << 1) ^ (number >> 127)) as u128);
+            }
+            MalType::Float(number) => {
+                self.body.push(FLOAT);
+                self.body.extend(number.to_le_bytes());
+            }
+            MalType::String(string) => self.string(STRING, string),
+            MalType::Symbol(symbol) => self.string(SYMBOL, symbol),
+            MalType::Regex(regex) => self.string(REGEX, regex.as_str()),
+            MalType::List { tokens } => {
+                self.collection(value, LIST, tokens.len(), tokens.iter())?
+            }
+            MalType::Vector { tokens } => {
+                self.collection(value, VECTOR, tokens.len(), tokens.iter())?
+            }
+            // Hash-maps are written as their keys and values interleaved.
+            MalType::HashMap { entries } => self.collection(
+                value,
+                HASHMAP,
+                entries.len() * 2,
+                entries.iter().flat_map(|(key, value)| [key, value]),
+            )?,
+            MalType::Set { members } => {
+                self.collection(value, SET, members.len(), members.iter())?
+            }
+            MalType::Atom(atom) => match self.atom_ids.get(&Rc::as_ptr(atom)) {
+                Some(&id) => {
+                    self.body.push(ATOM_REF);
+                    write_varint(&mut self.body, id as u128);
+                }
+                None => {
+                    self.atom_ids.insert(Rc::as_ptr(atom), self.atom_ids.len());
+                    self.body.push(ATOM);
+                    self.value(&atom.borrow())?;
+                }
+            },
+            MalType::Function { .. } => {
+                return Err(ReplError::Type {
+                    expected: "serializable value".into(),
+                    received: value.to_string().into(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    depth: usize,
+    strings: Vec<Rc<str>>,
+    atoms: Vec<Rc<RefCell<MalType>>>,
+    collections: Vec<MalType>,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ReplError> {
+        if len > self.bytes.len() {
+            return Err(invalid("unexpected end of data"));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<u128, ReplError> {
+        let mut value = 0u128;
+        for shift in (0..128).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u128::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("varint is too long"))
+    }
+
+    fn len(&mut self) -> Result<usize, ReplError> {
+        usize::try_from(self.varint()?).map_err(|_| invalid("length is too large"))
+    }
+
+    fn string(&mut self) -> Result<Rc<str>, ReplError> {
+        let id = self.len()?;
+        self.strings
+            .get(id)
+            .cloned()
+            .ok_or_else(|| invalid("string index out of range"))
+    }
+
+    fn collection(&mut self) -> Result<Tokens, ReplError> {
+        let len = self.len()?;
+        let mut tokens = Tokens::new();
+        for _ in 0..len {
+            tokens.push_back(self.value()?);
+        }
+        Ok(tokens)
+    }
+
+    fn value(&mut self) -> Result<MalType, ReplError> {
+        if self.depth >= reader::max_depth() {
+            return Err(ReplError::Depth(reader::max_depth()));
+        }
+        self.depth += 1;
+        let value = self.nested_value();
+        self.depth -= 1;
+        value
+    }
+
+    fn nested_value(&mut self) -> Result<MalType, ReplError> {
+        let tag = self.take(1)?[0];
+        let value = match tag {
+            NIL => MalType::Nil,
+            FALSE => MalType::Bool(false),
+            TRUE => MalType::Bool(true),
+            NUMBER => {
+                let zigzag = self.varint()?;
+                MalType::Number((zigzag >> 1) as i128 ^ -((zigzag & 1) as i128))
+            }
+            FLOAT => MalType::Float(f64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            STRING => MalType::String(self.string()?),
+            SYMBOL => MalType::Symbol(self.string()?.as_ref().into()),
+            REGEX => match Regex::new(&self.string()?) {
+                Ok(regex) => MalType::Regex(regex),
+                Err(e) => return Err(ReplError::Regex(e.to_string().into())),
+            },
+            LIST | VECTOR | HASHMAP | SET => {
+                let tokens = self.collection()?;
+                let empty = tokens.is_empty();
+                let collection = match tag {
+                    LIST => MalType::List { tokens },
+                    VECTOR => MalType::Vector { tokens },
+                    HASHMAP if tokens.len() % 2 == 1 => {
+                        return Err(invalid("hash-map with a key and no value"));
+                    }
+                    HASHMAP => MalType::HashMap {
+                        entries: types::pairs(&tokens)
+                            .map(|(key, value)| (key.clone(), value.clone()))
+                            .collect(),
+                    },
+                    _ => MalType::Set {
+                        members: tokens.into_iter().collect(),
+                    },
+                };
+                if !empty {
+                    self.collections.push(collection.clone());
+                }
+                collection
+            }
+            ATOM => {
+                let atom = Rc::new(RefCell::new(MalType::Nil));
+                self.atoms.push(atom.clone());
+                let value = self.value()?;
+                atom.replace(value);
+                MalType::Atom(atom)
+            }
+            ATOM_REF => {
+                let id = self.len()?;
+                match self.atoms.get(id) {
+                    Some(atom) => MalType::Atom(atom.clone()),
+                    None => return Err(invalid("atom reference out of range")),
+                }
+            }
+            COLLECTION_REF => {
+                let id = self.len()?;
+                match self.collections.get(id) {
+                    Some(collection) => collection.clone(),
+                    None => return Err(invalid("collection reference out of range")),
+                }
+            }
+            tag => return Err(invalid(&format!("unknown tag {tag}"))),
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use im_rc::vector;
+
+    use crate::parser::{edn, print_str};
+
+    fn round_trip(value: &MalType) -> MalType {
+        deserialize(&serialize(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn round_trips_every_kind() {
+        let value = edn::read(
+            r#"{:k [sym "str" "" 0 -1 -170141183460469231731687303715884105728 -2.5 -0.0 nil true false] :l (1 (2)) #{:a} {[1] {"nested" ()}}}"#,
+        )
+        .unwrap();
+        assert_eq!(round_trip(&value), value);
+
+        let regex = reader::read_string(r#"#"a\"b+""#).unwrap();
+        assert_eq!(print_str(round_trip(&regex)), print_str(regex));
+    }
+
+    #[test]
+    fn keeps_atoms_shared_and_cyclic() {
+        let atom = Rc::new(RefCell::new(MalType::Number(1)));
+        let value = MalType::Vector {
+            tokens: vector![MalType::Atom(atom.clone()), MalType::Atom(atom.clone())],
+        };
+        atom.replace(value.clone());
+
+        let MalType::Vector { tokens } = round_trip(&value) else {
+            panic!("expected a vector");
+        };
+        let (MalType::Atom(first), MalType::Atom(second)) = (&tokens[0], &tokens[1]) else {
+            panic!("expected atoms");
+        };
+        assert!(Rc::ptr_eq(first, second));
+        assert_eq!(
+            print_str(tokens[0].clone()),
+            "(atom [(atom ...) (atom ...)])"
+        );
+    }
+
+    #[test]
+    fn numbers_collections_finished_inside_their_own_atoms() {
+        let atom = Rc::new(RefCell::new(MalType::Nil));
+        let inner = MalType::Vector {
+            tokens: vector![MalType::Atom(atom.clone())],
+        };
+        atom.replace(inner.clone());
+        let seven = MalType::Vector {
+            tokens: vector![MalType::Number(7)],
+        };
+        let value = MalType::Vector {
+            tokens: vector![inner, seven.clone(), seven],
+        };
+
+        assert_eq!(
+            print_str(round_trip(&value)),
+            "[[(atom [(atom ...)])] [7] [7]]"
+        );
+    }
+
+    #[test]
+    fn writes_repeated_collections_once() {
+        let row = edn::read(r#"{:name "a long enough name" :tags [:x :y :z]}"#).unwrap();
+        let one = serialize(&MalType::Vector {
+            tokens: vector![row.clone()],
+        })
+        .unwrap();
+        let value = MalType::Vector {
+            tokens: (0..100).map(|_| row.clone()).collect(),
+        };
+        let many = serialize(&value).unwrap();
+
+        assert!(many.len() < one.len() + 100 * 3, "{} bytes", many.len());
+        assert_eq!(round_trip(&value), value);
+    }
+
+    #[test]
+    fn rejects_truncated_and_corrupt_input() {
+        let bytes = serialize(&edn::read(r#"[1 "two" #{3} {4 5}]"#).unwrap()).unwrap();
+        for len in 0..bytes.len() {
+            assert!(
+                matches!(deserialize(&bytes[..len]), Err(ReplError::Conversion(_))),
+                "{len} bytes"
+            );
+        }
+
+        let mut trailing = bytes.clone();
+        trailing.push(NIL);
+        assert!(deserialize(&trailing).is_err());
+
+        let mut version = bytes.clone();
+        version[4] = VERSION + 1;
+        assert!(deserialize(&version).is_err());
+
+        let header = [&MAGIC[..], &[VERSION, 0]].concat();
+        for body in [
+            &[COLLECTION_REF, 0][..],
+            &[ATOM_REF, 0],
+            &[STRING, 0],
+            &[HASHMAP, 1, NIL],
+            &[99],
+        ] {
+            assert!(deserialize(&[&header[..], body].concat()).is_err());
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use regex::Regex;
+
+use serde::de::value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{
+    self, DeserializeOwned, Deserializer, EnumAccess, IntoDeserializer, VariantAccess, Visitor,
+};
+use serde::ser::{self, Serializer};
+use serde::{Deserialize, Serialize, forward_to_deserialize_any};
+
+use crate::parser::errors::ReplError;
+use crate::parser::types::MalType;
+
+// Values are written as an externally tagged enum with one variant per kind, so a value read
+// back has the same kind as the one written. Hash-maps are sequences of key and value pairs,
+// since most formats only allow string keys.
+const VARIANTS: &[&str] = &[
+    "Nil", "Bool", "Number", "Float", "String", "Symbol", "List", "Vector", "HashMap", "Set",
+    "Regex", "Atom",
+];
+
+thread_local! {
+    // Atoms currently being serialized or deserialized from, to stop at a cycle.
+    static ATOMS: RefCell<Vec<*const RefCell<MalType>>> = const { RefCell::new(Vec::new()) };
+}
+
+fn with_atom<T, E>(
+    atom: &Rc<RefCell<MalType>>,
+    error: impl FnOnce(&'static str) -> E,
+    f: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let ptr = Rc::as_ptr(atom);
+    if ATOMS.with(|atoms| atoms.borrow().contains(&ptr)) {
+        return Err(error("atom contains itself"));
+    }
+    ATOMS.with(|atoms| atoms.borrow_mut().push(ptr));
+    let result = f();
+    ATOMS.with(|atoms| atoms.borrow_mut().pop());
+    result
+}
+
+// Writes any collection as a sequence, so hash-maps become sequences of key and value pairs.
+struct Seq<'a, T>(&'a T);
+
+impl<'a, T> Serialize for Seq<'a, T>
+where
+    &'a T: IntoIterator<Item: Serialize>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0)
+    }
+}
+
+impl Serialize for MalType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let name = "MalType";
+        match self {
+            MalType::Nil => serializer.serialize_unit_variant(name, 0, "Nil"),
+            MalType::Bool(boolean) => {
+                serializer.serialize_newtype_variant(name, 1, "Bool", boolean)
+            }
+            MalType::Number(number) => {
+                serializer.serialize_newtype_variant(name, 2, "Number", number)
+            }
+            MalType::Float(number) => {
+                serializer.serialize_newtype_variant(name, 3, "Float", number)
+            }
+            MalType::String(string) => {
+                serializer.serialize_newtype_variant(name, 4, "String", &**string)
+            }
+            MalType::Symbol(symbol) => {
+                serializer.serialize_newtype_variant(name, 5, "Symbol", symbol.as_str())
+            }
+            MalType::List { tokens } => {
+                serializer.serialize_newtype_variant(name, 6, "List", &Seq(tokens))
+            }
+            MalType::Vector { tokens } => {
+                serializer.serialize_newtype_variant(name, 7, "Vector", &Seq(tokens))
+            }
+            MalType::HashMap { entries } => {
+                serializer.serialize_newtype_variant(name, 8, "HashMap", &Seq(entries))
+            }
+            MalType::Set { members } => {
+                serializer.serialize_newtype_variant(name, 9, "Set", &Seq(members))
+            }
+            MalType::Regex(regex) => {
+                serializer.serialize_newtype_variant(name, 10, "Regex", regex.as_str())
+            }
+            MalType::Atom(atom) => with_atom(atom, ser::Error::custom, || {
+                serializer.serialize_newtype_variant(name, 11, "Atom", &*atom.borrow())
+            }),
+            MalType::Function { name, .. } => Err(ser::Error::custom(format!(
+                "builtin {name} cannot be serialized"
+            ))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MalType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_enum("MalType", VARIANTS, MalTypeVisitor)
+    }
+}
+
+struct MalTypeVisitor;
+
+impl<'de> Visitor<'de> for MalTypeVisitor {
+    type Value = MalType;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a mal value")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<MalType, A::Error> {
+        let (Variant(variant), access) = data.variant()?;
+        let value = match variant {
+            "Nil" => {
+                access.unit_variant()?;
+                MalType::Nil
+            }
+            "Bool" => MalType::Bool(access.newtype_variant()?),
+            "Number" => MalType::Number(access.newtype_variant()?),
+            "Float" => MalType::Float(access.newtype_variant()?),
+            "String" => MalType::String(access.newtype_variant::<String>()?.into()),
+            "Symbol" => MalType::Symbol(access.newtype_variant::<String>()?.into()),
+            "List" => MalType::List {
+                tokens: access.newtype_variant::<Vec<MalType>>()?.into(),
+            },
+            "Vector" => MalType::Vector {
+                tokens: access.newtype_variant::<Vec<MalType>>()?.into(),
+            },
+            "HashMap" => MalType::HashMap {
+                entries: access
+                    .newtype_variant::<Vec<(MalType, MalType)>>()?
+                    .into_iter()
+                    .collect(),
+            },
+            "Set" => MalType::Set {
+                members: access
+                    .newtype_variant::<Vec<MalType>>()?
+                    .into_iter()
+                    .collect(),
+            },
+            "Regex" => {
+                let pattern: String = access.newtype_variant()?;
+                MalType::Regex(Regex::new(&pattern).map_err(de::Error::custom)?)
+            }
+            "Atom" => MalType::Atom(Rc::new(RefCell::new(access.newtype_variant()?))),
+            _ => unreachable!("Variant only holds names from VARIANTS"),
+        };
+        Ok(value)
+    }
+}
+
+// The name of a variant in VARIANTS. Self-describing formats write variants by name, compact ones
+// such as bincode by their index.
+struct Variant(&'static str);
+
+impl<'de> Deserialize<'de> for Variant {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_identifier(VariantVisitor)
+    }
+}
+
+struct VariantVisitor;
+
+impl<'de> Visitor<'de> for VariantVisitor {
+    type Value = Variant;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a mal value kind")
+    }
+
+    fn visit_u64<E: de::Error>(self, index: u64) -> Result<Variant, E> {
+        match VARIANTS.get(index as usize) {
+            Some(name) => Ok(Variant(name)),
+            None => Err(de::Error::invalid_value(
+                de::Unexpected::Unsigned(index),
+                &self,
+            )),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<Variant, E> {
+        match VARIANTS.iter().find(|&&variant| variant == name) {
+            Some(name) => Ok(Variant(name)),
+            None => Err(de::Error::unknown_variant(name, VARIANTS)),
+        }
+    }
+
+    fn visit_bytes<E: de::Error>(self, name: &[u8]) -> Result<Variant, E> {
+        match str::from_utf8(name) {
+            Ok(name) => self.visit_str(name),
+            Err(_) => Err(de::Error::invalid_value(de::Unexpected::Bytes(name), &self)),
+        }
+    }
+}
+
+impl de::Error for ReplError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        ReplError::Conversion(message.to_string().into())
+    }
+}
+
+// Deserializes any Rust type from a mal value, e.g. a struct from a hash-map whose keys are
+// keywords or strings naming its fields.
+pub fn from_value<T: DeserializeOwned>(value: MalType) -> Result<T, ReplError> {
+    T::deserialize(value)
+}
+
+impl<'de> IntoDeserializer<'de, ReplError> for MalType {
+    type Deserializer = MalType;
+
+    fn into_deserializer(self) -> MalType {
+        self
+    }
+}
+
+fn variant<'de, V: Visitor<'de>>(name: &str, visitor: V) -> Result<V::Value, ReplError> {
+    let name = name.strip_prefix(':').unwrap_or(name);
+    visitor.visit_enum(IntoDeserializer::<ReplError>::into_deserializer(name))
+}
+
+impl<'de> Deserializer<'de> for MalType {
+    type Error = ReplError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ReplError> {
+        match self {
+            MalType::Nil => visitor.visit_unit(),
+            MalType::Bool(boolean) => visitor.visit_bool(boolean),
+            MalType::Number(number) => match (i64::try_from(number), u64::try_from(number)) {
+                (Ok(number), _) => visitor.visit_i64(number),
+                (_, Ok(number)) => visitor.visit_u64(number),
+                _ => visitor.visit_i128(number),
+            },
+            MalType::Float(number) => visitor.visit_f64(number),
+            MalType::String(string) => visitor.visit_str(&string),
+            MalType::Symbol(symbol) => {
+                visitor.visit_str(symbol.strip_prefix(':').unwrap_or(&symbol))
+            }
+            MalType::List { tokens } | MalType::Vector { tokens } => {
+                let mut seq = SeqDeserializer::new(tokens.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            MalType::Set { members } => {
+                let mut seq = SeqDeserializer::new(members.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            MalType::HashMap { entries } => {
+                let mut map = MapDeserializer::new(entries.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            MalType::Regex(regex) => visitor.visit_str(regex.as_str()),
+            MalType::Atom(atom) => with_atom(&atom, de::Error::custom, || {
+                atom.borrow().clone().deserialize_any(visitor)
+            }),
+            other @ MalType::Function { .. } => Err(ReplError::Conversion(
+                format!("{} cannot be deserialized", other).into(),
+            )),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ReplError> {
+        match self {
+            MalType::Nil => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    // Unit variants are written as keywords or strings, the others as single-entry maps from
+    // the variant name to its contents.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ReplError> {
+        match self {
+            MalType::HashMap { entries } if entries.len() == 1 => visitor.visit_enum(
+                MapAccessDeserializer::new(MapDeserializer::new(entries.into_iter())),
+            ),
+            MalType::String(name) => variant(&name, visitor),
+            MalType::Symbol(name) => variant(&name, visitor),
+            other => Err(ReplError::Type {
+                expected: "enum variant".into(),
+                received: other.to_string().into(),
+            }),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ReplError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::parser::{edn, print_str, reader};
+
+    fn round_trip(value: &MalType) -> MalType {
+        let json = serde_json::to_string(value).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn round_trips_through_formats_that_write_variant_indices() {
+        let value = edn::read(r#"{:k [sym "str" 1 -2.5 nil true] (1 2) #{:a {[1] ()}}}"#).unwrap();
+        let bytes = bincode::serialize(&value).unwrap();
+        assert_eq!(bincode::deserialize::<MalType>(&bytes).unwrap(), value);
+
+        let mut unknown = bincode::serialize(&MalType::Nil).unwrap();
+        unknown[0] = VARIANTS.len() as u8;
+        assert!(bincode::deserialize::<MalType>(&unknown).is_err());
+    }
+
+    #[test]
+    fn keeps_the_kind_of_every_value() {
+        let value = edn::read(
+            r#"{:k [sym "str" 1 -2.5 nil true] (1 2) #{:a} {[1] {"nested" ()}} 170141183460469231731687303715884105727}"#,
+        )
+        .unwrap();
+        let back = round_trip(&value);
+        assert_eq!(back, value);
+        assert_eq!(print_str(back), print_str(value));
+    }
+
+    #[test]
+    fn keeps_regexes_and_atoms() {
+        let regex = reader::read_string(r#"#"a+\d""#).unwrap();
+        assert_eq!(round_trip(&regex), regex);
+
+        let atom = MalType::Atom(Rc::new(RefCell::new(MalType::Symbol(":x".into()))));
+        let MalType::Atom(back) = round_trip(&atom) else {
+            panic!("expected an atom");
+        };
+        assert_eq!(*back.borrow(), MalType::Symbol(":x".into()));
+    }
+
+    #[test]
+    fn refuses_self_referencing_atoms() {
+        let atom = Rc::new(RefCell::new(MalType::Nil));
+        atom.replace(MalType::Vector {
+            tokens: [MalType::Atom(atom.clone())].into_iter().collect(),
+        });
+        let value = MalType::Atom(atom.clone());
+        assert!(serde_json::to_string(&value).is_err());
+        assert!(from_value::<Vec<i64>>(value).is_err());
+        atom.replace(MalType::Nil);
+    }
+
+    #[test]
+    fn refuses_builtins() {
+        let function = MalType::Function {
+            name: "identity",
+            func: Ok,
+        };
+        assert!(serde_json::to_string(&function).is_err());
+    }
+
+    #[test]
+    fn converts_values_into_rust_types() {
+        let value = edn::read(r#"{:a [1 2] "b" [3]}"#).unwrap();
+        let map: HashMap<String, Vec<i64>> = from_value(value).unwrap();
+        assert_eq!(map["a"], [1, 2]);
+        assert_eq!(map["b"], [3]);
+
+        assert_eq!(from_value::<Option<String>>(MalType::Nil).unwrap(), None);
+        assert!(from_value::<u8>(MalType::Number(256)).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use std::fmt::{self, Display};
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::parser::errors::ReplError;
+use crate::parser::reader;
+
+// Unlike the reader's tokenizer every byte of the source ends up in some token, so the tree can
+// be printed back exactly as it was read.
+static TOKEN_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"\s+|,+|;[^\n]*|~@|#[{(_]|#"(?:\\.|[^\\"])*"?|[\[\]{}()'`~^@]|"(?:\\.|[^\\"])*"?|[^\s\[\]{}('"`,;)]+"#,
+    )
+    .unwrap()
+});
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    Whitespace(Box<str>),
+    Comma(Box<str>),
+    Comment(Box<str>),
+    Atom(Box<str>),
+    Prefix {
+        prefix: Box<str>,
+        children: Vec<Node>,
+    },
+    Collection {
+        open: Box<str>,
+        children: Vec<Node>,
+        close: Box<str>,
+    },
+}
+
+impl Node {
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            Node::Whitespace(_) | Node::Comma(_) | Node::Comment(_)
+        )
+    }
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Whitespace(text) | Node::Comma(text) | Node::Comment(text) | Node::Atom(text) => {
+                f.write_str(text)
+            }
+            Node::Prefix { prefix, children } => {
+                f.write_str(prefix)?;
+                children.iter().try_for_each(|child| child.fmt(f))
+            }
+            Node::Collection {
+                open,
+                children,
+                close,
+            } => {
+                f.write_str(open)?;
+                children.iter().try_for_each(|child| child.fmt(f))?;
+                f.write_str(close)
+            }
+        }
+    }
+}
+
+struct Tokens<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+}
+
+pub fn parse(src: &str) -> Result<Vec<Node>, ReplError> {
+    let mut nodes = Vec::new();
+
+    let src = match src.strip_prefix("#!") {
+        Some(_) => {
+            let end = src.find('\n').unwrap_or(src.len());
+            nodes.push(Node::Comment(src[..end].into()));
+            &src[end..]
+        }
+        None => src,
+    };
+
+    let mut tokens = Tokens {
+        tokens: TOKEN_RE
+            .find_iter(src)
+            .map(|token| token.as_str())
+            .collect(),
+        pos: 0,
+        depth: 0,
+    };
+
+    while tokens.peek().is_some() {
+        nodes.push(parse_node(&mut tokens)?);
+    }
+
+    Ok(nodes)
+}
+
+pub fn print(nodes: &[Node]) -> String {
+    nodes.iter().map(Node::to_string).collect()
+}
+
+fn parse_node(tokens: &mut Tokens) -> Result<Node, ReplError> {
+    let max_depth = reader::max_depth();
+    if tokens.depth >= max_depth {
+        return Err(ReplError::Depth(max_depth));
+    }
+    tokens.depth += 1;
+    let node = parse_nested_node(tokens);
+    tokens.depth -= 1;
+    node
+}
+
+fn parse_nested_node(tokens: &mut Tokens) -> Result<Node, ReplError> {
+    let token = tokens.next().ok_or(ReplError::Eof)?;
+
+    let node = match token {
+        "(" | "#(" => parse_collection(tokens, token, ")")?,
+        "[" => parse_collection(tokens, token, "]")?,
+        "{" | "#{" => parse_collection(tokens, token, "}")?,
+        ")" | "]" | "}" => return Err(ReplError::Unexpected(token.chars().next().unwrap())),
+        "'" | "`" | "~" | "~@" | "@" | "#_" => parse_prefix(tokens, token, 1)?,
+        "^" => parse_prefix(tokens, token, 2)?,
+        _ if token.starts_with(char::is_whitespace) => Node::Whitespace(token.into()),
+        _ if token.starts_with(',') => Node::Comma(token.into()),
+        _ if token.starts_with(';') => Node::Comment(token.into()),
+        _ => Node::Atom(token.into()),
+    };
+
+    Ok(node)
+}
+
+fn parse_collection(tokens: &mut Tokens, open: &str, close: &str) -> Result<Node, ReplError> {
+    let mut children = Vec::new();
+    loop {
+        match tokens.peek() {
+            Some(token) if token == close => break,
+            Some(_) => children.push(parse_node(tokens)?),
+            None => return Err(ReplError::Unclosed(close.chars().next().unwrap())),
+        }
+    }
+    let close = tokens.next().unwrap();
+
+    Ok(Node::Collection {
+        open: open.into(),
+        children,
+        close: close.into(),
+    })
+}
+
+fn parse_prefix(tokens: &mut Tokens, prefix: &str, forms: usize) -> Result<Node, ReplError> {
+    let mut children = Vec::new();
+    let mut read = 0;
+    while read < forms {
+        let node = match parse_node(tokens) {
+            Ok(node) => node,
+            Err(ReplError::Eof) => return Err(ReplError::Arguments(prefix.into())),
+            Err(e) => return Err(e),
+        };
+        if !node.is_trivia() {
+            read += 1;
+        }
+        children.push(node);
+    }
+
+    Ok(Node::Prefix {
+        prefix: prefix.into(),
+        children,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    fn round_trip(src: &str) {
+        assert_eq!(print(&parse(src).unwrap()), src);
+    }
+
+    #[test]
+    fn prints_trivia_back_unchanged() {
+        round_trip("");
+        round_trip("  (a ,, b)\t; comment\n\n[1 2]  ");
+        round_trip("#!/usr/bin/env mal\n(prn 1)");
+        round_trip("{:a 1,\n :b 2}\r\n");
+        round_trip("'a `(b ~c ~@d) @e ^{:m 1} f #_ g");
+        round_trip("#{1 2} #(+ % 1) #\"\\d+\" \"a \\\"quoted\\\" string\"");
+    }
+
+    #[test]
+    fn round_trips_the_test_suite() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../tests");
+        let mut parsed = 0;
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "mal") {
+                continue;
+            }
+            let src = fs::read_to_string(&path).unwrap();
+            // Some test files deliberately contain unbalanced input.
+            if let Ok(nodes) = parse(&src) {
+                assert_eq!(print(&nodes), src, "{}", path.display());
+                parsed += 1;
+            }
+        }
+        assert!(parsed > 0);
+    }
+
+    #[test]
+    fn groups_prefixes_with_their_form() {
+        let nodes = parse("'(a)").unwrap();
+        assert!(matches!(&nodes[..], [Node::Prefix { prefix, children }]
+            if &**prefix == "'" && matches!(&children[..], [Node::Collection { .. }])));
+    }
+
+    #[test]
+    fn reports_unclosed_collections() {
+        assert!(matches!(parse("(a [b)"), Err(ReplError::Unexpected(')'))));
+        assert!(parse("(a").is_err());
+    }
+}
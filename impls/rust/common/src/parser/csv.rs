@@ -0,0 +1,291 @@
+use std::rc::Rc;
+
+use crate::parser::errors::ReplError;
+use crate::parser::print_str;
+use crate::parser::types::{MalType, Tokens};
+
+// A record's fields and the line it starts on.
+type Record = (usize, Vec<Rc<str>>);
+
+// Rows are read into vectors of strings, or into hash-maps keyed by the header's fields as
+// keywords when `header` is set. Blank lines are skipped.
+pub fn read(src: &str, separator: char, header: bool) -> Result<MalType, ReplError> {
+    let mut records = records(src, separator)?;
+    if !header {
+        let tokens = records
+            .into_iter()
+            .map(|(_, fields)| MalType::Vector {
+                tokens: fields.into_iter().map(MalType::String).collect(),
+            })
+            .collect();
+        return Ok(MalType::Vector { tokens });
+    }
+
+    if records.is_empty() {
+        return Ok(MalType::Vector {
+            tokens: Tokens::new(),
+        });
+    }
+    let (_, keys) = records.remove(0);
+    let keys: Vec<MalType> = keys
+        .iter()
+        .map(|key| MalType::Symbol(format!(":{key}").into()))
+        .collect();
+
+    let mut rows = Tokens::new();
+    for (line, fields) in records {
+        if fields.len() != keys.len() {
+            return Err(ReplError::Csv {
+                message: format!("expected {} fields, found {}", keys.len(), fields.len()).into(),
+                line,
+            });
+        }
+        let entries = keys
+            .iter()
+            .cloned()
+            .zip(fields.into_iter().map(MalType::String))
+            .collect();
+        rows.push_back(MalType::HashMap { entries });
+    }
+    Ok(MalType::Vector { tokens: rows })
+}
+
+fn records(src: &str, separator: char) -> Result<Vec<Record>, ReplError> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+
+    let mut chars = src.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            '"' if field.is_empty() && !quoted => {
+                quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(char) => {
+                            if char == '\n' {
+                                line += 1;
+                            }
+                            field.push(char);
+                        }
+                        None => {
+                            return Err(ReplError::Csv {
+                                message: "unterminated quoted field".into(),
+                                line: start,
+                            });
+                        }
+                    }
+                }
+                match chars.peek() {
+                    None | Some('\r' | '\n') => {}
+                    Some(&next) if next == separator => {}
+                    Some(_) => {
+                        return Err(ReplError::Csv {
+                            message: "unexpected character after closing quote".into(),
+                            line,
+                        });
+                    }
+                }
+            }
+            '"' => {
+                return Err(ReplError::Csv {
+                    message: "quote inside unquoted field".into(),
+                    line,
+                });
+            }
+            char if char == separator => {
+                fields.push(std::mem::take(&mut field).into());
+                quoted = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                if !fields.is_empty() || !field.is_empty() || quoted {
+                    fields.push(std::mem::take(&mut field).into());
+                    records.push((start, std::mem::take(&mut fields)));
+                }
+                quoted = false;
+                line += 1;
+                start = line;
+            }
+            char => field.push(char),
+        }
+    }
+
+    if !fields.is_empty() || !field.is_empty() || quoted {
+        fields.push(field.into());
+        records.push((start, fields));
+    }
+    Ok(records)
+}
+
+// Rows are vectors or lists of fields, or hash-maps whose keys, taken from the first row, become
+// the header. Records end with CRLF as in RFC 4180.
+pub fn write(rows: &MalType, separator: char) -> Result<String, ReplError> {
+    let rows = match rows {
+        MalType::List { tokens } | MalType::Vector { tokens } => tokens,
+        other => return Err(type_error("list or vector of rows", other)),
+    };
+
+    let mut out = String::new();
+    let keys: Option<Vec<&MalType>> = match rows.front() {
+        Some(MalType::HashMap { entries }) => Some(entries.keys().collect()),
+        _ => None,
+    };
+    if let Some(keys) = &keys {
+        let header: Vec<String> = keys.iter().map(|key| field(key)).collect();
+        write_record(&mut out, &header, separator);
+    }
+
+    for row in rows {
+        let fields: Vec<String> = match (row, &keys) {
+            (MalType::HashMap { entries }, Some(keys)) => keys
+                .iter()
+                .map(|&key| entries.get(key).map_or(String::new(), field))
+                .collect(),
+            (MalType::List { tokens } | MalType::Vector { tokens }, None) => {
+                tokens.iter().map(field).collect()
+            }
+            (other, Some(_)) => return Err(type_error("hashmap", other)),
+            (other, None) => return Err(type_error("list or vector", other)),
+        };
+        write_record(&mut out, &fields, separator);
+    }
+
+    Ok(out)
+}
+
+fn type_error(expected: &str, received: &MalType) -> ReplError {
+    ReplError::Type {
+        expected: expected.into(),
+        received: received.to_string().into(),
+    }
+}
+
+fn field(value: &MalType) -> String {
+    match value {
+        MalType::String(string) => string.to_string(),
+        MalType::Symbol(symbol) => symbol.strip_prefix(':').unwrap_or(symbol).to_string(),
+        MalType::Nil => String::new(),
+        value => print_str(value.clone()),
+    }
+}
+
+fn write_record(out: &mut String, fields: &[String], separator: char) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(separator);
+        }
+        // A lone empty field is quoted so the record is not read back as a blank line.
+        if field.contains([separator, '"', '\r', '\n']) || (fields.len() == 1 && field.is_empty()) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::edn;
+
+    fn read_str(src: &str, header: bool) -> String {
+        print_str(read(src, ',', header).unwrap())
+    }
+
+    fn error_line(src: &str, header: bool) -> usize {
+        match read(src, ',', header) {
+            Err(ReplError::Csv { line, .. }) => line,
+            other => panic!("expected a CSV error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn reads_quoted_fields() {
+        assert_eq!(
+            read_str(r#"a,"b,c","say ""hi""",,"""#, false),
+            r#"[["a" "b,c" "say \"hi\"" "" ""]]"#
+        );
+        assert_eq!(read_str("a;b\n", false), r#"[["a;b"]]"#);
+        assert_eq!(
+            print_str(read("a;\"b;c\"", ';', false).unwrap()),
+            r#"[["a" "b;c"]]"#
+        );
+    }
+
+    #[test]
+    fn reads_newlines_inside_quotes() {
+        assert_eq!(
+            read_str("\"one\r\ntwo\",x\r\n\r\ny,\"\n\"\n", false),
+            r#"[["one\r\ntwo" "x"] ["y" "\n"]]"#
+        );
+        assert_eq!(error_line("a\n\"b\nc\nd", false), 2);
+        assert_eq!(error_line("\"a\nb\",c\nd\"e", false), 3);
+    }
+
+    #[test]
+    fn reads_headers_as_keywords() {
+        assert_eq!(
+            read_str("name,age\r\nann,3\n\nbob,\n", true),
+            r#"[{:age "3" :name "ann"} {:age "" :name "bob"}]"#
+        );
+        assert_eq!(read_str("name,age\n", true), "[]");
+        assert_eq!(read_str("", true), "[]");
+    }
+
+    #[test]
+    fn rejects_ragged_rows_only_with_a_header() {
+        assert_eq!(
+            read_str("a,b\nc\nd,e,f", false),
+            r#"[["a" "b"] ["c"] ["d" "e" "f"]]"#
+        );
+        assert_eq!(error_line("a,b\nc,d\n\"e\n\"", true), 3);
+        assert_eq!(error_line("a,b\nc,d,e", true), 2);
+    }
+
+    #[test]
+    fn rejects_stray_quotes() {
+        assert_eq!(error_line("a,b\"c", false), 1);
+        assert_eq!(error_line("a\n\"b\"c", false), 2);
+    }
+
+    #[test]
+    fn writes_records_that_read_back() {
+        let rows = edn::read(r#"[["a" "b,c" "say \"hi\"" "x\ny"] [1 :k nil ""] [""]]"#).unwrap();
+        let out = write(&rows, ',').unwrap();
+        assert_eq!(
+            out,
+            "a,\"b,c\",\"say \"\"hi\"\"\",\"x\ny\"\r\n1,k,,\r\n\"\"\r\n"
+        );
+        assert_eq!(
+            read_str(&out, false),
+            r#"[["a" "b,c" "say \"hi\"" "x\ny"] ["1" "k" "" ""] [""]]"#
+        );
+    }
+
+    #[test]
+    fn writes_a_header_from_the_first_map() {
+        let rows = edn::read(r#"[{:name "ann" :age 3} {:name "bob"} {:age 4 :other 5}]"#).unwrap();
+        let out = write(&rows, ',').unwrap();
+        assert_eq!(out.lines().count(), 4);
+        assert_eq!(
+            read(&out, ',', true).unwrap(),
+            edn::read(r#"[{:name "ann" :age "3"} {:name "bob" :age ""} {:name "" :age "4"}]"#)
+                .unwrap()
+        );
+
+        let mixed = edn::read(r#"[{:a 1} [2]]"#).unwrap();
+        assert!(matches!(write(&mixed, ','), Err(ReplError::Type { .. })));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::parser::errors::ReplError;
+use crate::parser::print_str;
+use crate::parser::reader;
+use crate::parser::types::MalType;
+
+pub use crate::parser::types::Builtin as TagReader;
+
+// Reads a single EDN value. Nothing is evaluated: lists stay lists and symbols stay symbols.
+pub fn read(src: &str) -> Result<MalType, ReplError> {
+    let mut forms = reader::read_edn(src)?.into_iter();
+    match (forms.next(), forms.next()) {
+        (Some(form), None) => Ok(form),
+        (None, _) => Err(ReplError::Eof),
+        (Some(_), Some(_)) => Err(ReplError::Edn("expected a single value".into())),
+    }
+}
+
+pub fn read_all(src: &str) -> Result<Vec<MalType>, ReplError> {
+    reader::read_edn(src)
+}
+
+// Registers the reader for `#tag` literals, which receives the form following the tag.
+// Passing None removes it.
+pub fn set_tag(tag: &str, reader: Option<TagReader>) {
+    reader::set_tag(tag, reader);
+}
+
+pub fn write(value: &MalType) -> Result<String, ReplError> {
+    let mut out = String::new();
+    write_value(&mut out, value)?;
+    Ok(out)
+}
+
+fn write_value(out: &mut String, value: &MalType) -> Result<(), ReplError> {
+    let (open, elements, close): (_, Box<dyn Iterator<Item = &MalType>>, _) = match value {
+        MalType::List { tokens } => ("(", Box::new(tokens.iter()), ")"),
+        MalType::Vector { tokens } => ("[", Box::new(tokens.iter()), "]"),
+        MalType::HashMap { entries } => (
+            "{",
+            Box::new(entries.iter().flat_map(|(key, value)| [key, value])),
+            "}",
+        ),
+        MalType::Set { members } => ("#{", Box::new(members.iter()), "}"),
+        MalType::Float(num) if !num.is_finite() => {
+            out.push_str(match *num {
+                f64::INFINITY => "##Inf",
+                f64::NEG_INFINITY => "##-Inf",
+                _ => "##NaN",
+            });
+            return Ok(());
+        }
+        MalType::Function { .. } | MalType::Atom(_) | MalType::Regex(_) => {
+            return Err(ReplError::Type {
+                expected: "EDN value".into(),
+                received: value.to_string().into(),
+            });
+        }
+        MalType::String(string) => {
+            write_string(out, string);
+            return Ok(());
+        }
+        MalType::Symbol(symbol) => {
+            let (kind, name) = match symbol.strip_prefix(':') {
+                Some(name) => ("keyword", name),
+                None => ("symbol", symbol.as_str()),
+            };
+            if !is_edn_symbol(name)
+                || (kind == "symbol" && ["nil", "true", "false"].contains(&name))
+            {
+                return Err(ReplError::Edn(
+                    format!("{kind} {symbol} cannot be written as EDN").into(),
+                ));
+            }
+            out.push_str(symbol);
+            return Ok(());
+        }
+        value => {
+            out.push_str(&print_str(value.clone()));
+            return Ok(());
+        }
+    };
+
+    out.push_str(open);
+    for (i, element) in elements.enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        write_value(out, element)?;
+    }
+    out.push_str(close);
+    Ok(())
+}
+
+fn write_string(out: &mut String, string: &str) {
+    out.push('"');
+    for char in string.chars() {
+        match char {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\x08' => out.push_str("\\b"),
+            '\x0c' => out.push_str("\\f"),
+            char if char.is_control() => out.push_str(&format!("\\u{:04x}", char as u32)),
+            char => out.push(char),
+        }
+    }
+    out.push('"');
+}
+
+// EDN symbols, and keywords after their colon, are made of letters, digits and `.*+!-_?$%&=<>`,
+// plus `#` and `:` after the first character. A leading `-`, `+` or `.` must not be followed by
+// a digit, which would make a number. A single `/` separates a prefix from the name.
+fn is_edn_symbol(symbol: &str) -> bool {
+    fn is_name(name: &str) -> bool {
+        const PUNCTUATION: &str = ".*+!-_?$%&=<>";
+        let mut chars = name.chars();
+        let Some(first) = chars.next() else {
+            return false;
+        };
+        let starts_number =
+            "-+.".contains(first) && name[1..].starts_with(|char: char| char.is_ascii_digit());
+        (first.is_alphabetic() || PUNCTUATION.contains(first))
+            && !starts_number
+            && chars.all(|char| {
+                char.is_alphanumeric() || PUNCTUATION.contains(char) || "#:".contains(char)
+            })
+    }
+
+    match symbol.split_once('/') {
+        _ if symbol == "/" => true,
+        Some((prefix, name)) => is_name(prefix) && is_name(name),
+        None => is_name(symbol),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> MalType {
+        MalType::string(value)
+    }
+
+    #[test]
+    fn reads_characters_as_strings() {
+        let MalType::Vector { tokens } =
+            read(r"[\a \newline \space \tab \u0041 \u \( \) \, \[ \]]").unwrap()
+        else {
+            panic!("expected a vector");
+        };
+        let expected = ["a", "\n", " ", "\t", "A", "u", "(", ")", ",", "[", "]"].map(string);
+        assert!(tokens.iter().eq(expected.iter()));
+        assert_eq!(read(r"(\))").unwrap(), read(r#"(")")"#).unwrap());
+        assert!(matches!(read(r"\ab"), Err(ReplError::Edn(_))));
+        assert!(matches!(read(r"\ud800"), Err(ReplError::Edn(_))));
+    }
+
+    #[test]
+    fn reads_edn_string_escapes() {
+        assert_eq!(
+            read(r#""\u00e9\b\f\t\"\\ \ud83d\ude00""#).unwrap(),
+            string("é\x08\x0c\t\"\\ 😀")
+        );
+        for src in [r#""\u{e9}""#, r#""\u00""#, r#""\ud83d""#, r#""\0""#] {
+            assert!(matches!(read(src), Err(ReplError::Escape(_))), "{src}");
+        }
+    }
+
+    #[test]
+    fn reads_arbitrary_precision_suffixes() {
+        assert_eq!(
+            read("[1N -2N 1.5M 3M]").unwrap(),
+            read("[1 -2 1.5 3.0]").unwrap()
+        );
+        assert!(matches!(read("1.5N"), Err(ReplError::Number(_))));
+        assert!(matches!(read("1NN"), Err(ReplError::Number(_))));
+    }
+
+    #[test]
+    fn checks_built_in_tags() {
+        assert_eq!(
+            read(r#"#inst "1985-04-12T23:20:50.52Z""#).unwrap(),
+            string("1985-04-12T23:20:50.52Z")
+        );
+        assert_eq!(
+            read(r#"#uuid "f81d4fae-7dec-11d0-a765-00a0c91e6bf6""#).unwrap(),
+            string("f81d4fae-7dec-11d0-a765-00a0c91e6bf6")
+        );
+        assert!(matches!(
+            read(r#"#inst "yesterday""#),
+            Err(ReplError::Edn(_))
+        ));
+        assert!(matches!(read("#inst 1"), Err(ReplError::Edn(_))));
+        assert!(matches!(read("#inst"), Err(ReplError::Edn(_))));
+    }
+
+    #[test]
+    fn uses_registered_tag_readers() {
+        assert!(matches!(read("#point [1 2]"), Err(ReplError::Edn(_))));
+        set_tag(
+            "point",
+            Some(|value| {
+                Ok(MalType::List {
+                    tokens: [value].into_iter().collect(),
+                })
+            }),
+        );
+        assert_eq!(write(&read("#point [1 2]").unwrap()).unwrap(), "([1 2])");
+        set_tag("point", None);
+        assert!(matches!(read("#point [1 2]"), Err(ReplError::Edn(_))));
+    }
+
+    #[test]
+    fn rejects_code_and_malformed_maps() {
+        for src in [
+            "'a",
+            "`a",
+            "@a",
+            "#(inc %)",
+            "#\"re\"",
+            "{:a}",
+            "{:a 1 :a 2}",
+            "#{1 1}",
+        ] {
+            assert!(matches!(read(src), Err(ReplError::Edn(_))), "{src}");
+        }
+        assert!(matches!(read("1 2"), Err(ReplError::Edn(_))));
+        assert!(matches!(read(""), Err(ReplError::Eof)));
+    }
+
+    #[test]
+    fn writes_edn_escapes_and_refuses_names_edn_cannot_hold() {
+        let value = string("\u{1}\0\x08\x0c\t\"\\é");
+        let written = write(&value).unwrap();
+        assert_eq!(written, r#""\u0001\u0000\b\f\t\"\\é""#);
+        assert_eq!(read(&written).unwrap(), value);
+
+        for name in [":a/b", ":-a", ":a.b#c:d", "<=", "/", "ns/+"] {
+            let symbol = MalType::Symbol(name.into());
+            assert_eq!(write(&symbol).unwrap(), name);
+            assert_eq!(read(name).unwrap(), symbol);
+        }
+        for name in [
+            ":a b", ":", "::a", ":1a", "-1a", "a/b/c", "a/", "true", "(x)",
+        ] {
+            let symbol = MalType::Symbol(name.into());
+            assert!(matches!(write(&symbol), Err(ReplError::Edn(_))), "{name}");
+        }
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        let src = "[1 2.5 \"s\" #{nil} (x {:a :b})]";
+        assert_eq!(write(&read(src).unwrap()).unwrap(), src);
+        let value = read("{:a [1 2.5] :b #{nil true} :c (x y)}").unwrap();
+        assert_eq!(read(&write(&value).unwrap()).unwrap(), value);
+        assert_eq!(
+            write(&read("[##Inf ##-Inf]").unwrap()).unwrap(),
+            "[##Inf ##-Inf]"
+        );
+        assert_eq!(read_all("1 ; comment\n#_ 2 3").unwrap().len(), 2);
+    }
+}
@@ -1,7 +1,41 @@
-use std::{cell::RefCell, collections::HashMap};
+use std::{cell::RefCell, collections::HashMap, fs, rc::Rc};
 
-use crate::parser::{self, ENV, errors::ReplError, types::MalType};
-pub type Symbols = HashMap<Box<str>, MalType>;
+use im_rc::vector;
+
+use crate::parser::pretty::{self, Options};
+use crate::parser::reader::{self, ReaderMacro};
+use crate::parser::{
+    self, ENV,
+    errors::ReplError,
+    symbol::Symbol,
+    types::{Builtin, MalType},
+};
+use crate::parser::{binary, csv, edn, json};
+use crate::repl;
+pub type Symbols = HashMap<Symbol, MalType>;
+
+const BUILTINS: &[(&str, Builtin)] = &[
+    ("+", add),
+    ("-", sub),
+    ("*", mult),
+    ("/", div),
+    ("readline", readline),
+    ("load-file", load_file),
+    ("pprint", pprint),
+    ("set-reader-macro!", set_reader_macro),
+    ("atom", atom),
+    ("atom?", is_atom),
+    ("deref", deref),
+    ("reset!", reset),
+    ("json-parse", json_parse),
+    ("json-encode", json_encode),
+    ("read-edn", read_edn),
+    ("write-edn", write_edn),
+    ("serialize", serialize),
+    ("deserialize", deserialize),
+    ("csv-read", csv_read),
+    ("csv-write", csv_write),
+];
 
 #[derive(Debug)]
 pub struct Env {
@@ -10,19 +44,17 @@ pub struct Env {
 
 impl Env {
     pub fn new() -> Self {
-        let mut map: Symbols = HashMap::new();
-        map.insert("+".into(), MalType::Function(add));
-        map.insert("-".into(), MalType::Function(sub));
-        map.insert("*".into(), MalType::Function(mult));
-        map.insert("/".into(), MalType::Function(div));
-        #[cfg(debug_assertions)]
-        map.insert("DEBUG-EVAL".into(), MalType::Bool(true));
+        let map: Symbols = BUILTINS
+            .iter()
+            .map(|&(name, func)| (name.into(), MalType::Function { name, func }))
+            .chain(cfg!(debug_assertions).then(|| ("DEBUG-EVAL".into(), MalType::Bool(true))))
+            .collect();
         Env {
             envs: RefCell::new(vec![map]),
         }
     }
 
-    pub fn set(&self, key: Box<str>, value: MalType) {
+    pub fn set(&self, key: Symbol, value: MalType) {
         self.envs
             .borrow_mut()
             .last_mut()
@@ -30,9 +62,17 @@ impl Env {
             .insert(key, value);
     }
 
-    pub fn get(&self, key: &str) -> Option<MalType> {
+    pub fn set_global(&self, key: Symbol, value: MalType) {
+        self.envs
+            .borrow_mut()
+            .first_mut()
+            .unwrap()
+            .insert(key, value);
+    }
+
+    pub fn get(&self, key: Symbol) -> Option<MalType> {
         for map in self.envs.borrow().iter().rev() {
-            let value = map.get(key);
+            let value = map.get(&key);
             if value.is_some() {
                 return value.cloned();
             }
@@ -40,6 +80,18 @@ impl Env {
         None
     }
 
+    pub fn keys(&self) -> Vec<Symbol> {
+        self.envs
+            .borrow()
+            .iter()
+            .flat_map(|map| map.keys().copied())
+            .collect()
+    }
+
+    pub fn reset(&self) {
+        self.envs.replace(Env::new().envs.into_inner());
+    }
+
     pub fn new_env(&self) {
         self.envs.borrow_mut().push(HashMap::new())
     }
@@ -50,238 +102,420 @@ impl Env {
 }
 
 fn add(args: MalType) -> Result<MalType, ReplError> {
-    fn add_list(list: Vec<MalType>) -> Result<MalType, ReplError> {
-        if list.is_empty() {
-            return Err(ReplError::Arguments("+".into()));
-        }
-        let mut accumulator = 0;
-
-        for token in list {
-            match token {
-                MalType::Number(num) => accumulator += num,
-                MalType::List { tokens } => {
-                    let res = parser::eval(MalType::List { tokens })?;
-                    if let MalType::Number(num) = res {
-                        accumulator += num;
-                    }
-                }
-                MalType::Symbol(symbol) => {
-                    if let Some(value) = get_from_env(&symbol) {
-                        match value {
-                            MalType::Number(num) => accumulator += num,
-                            other => {
-                                return Err(ReplError::Type {
-                                    expected: "number".into(),
-                                    received: other.to_string().into(),
-                                });
-                            }
-                        }
-                    } else {
-                        return Err(ReplError::UnknownSymbol(symbol));
-                    }
-                }
-                other => {
-                    return Err(ReplError::Type {
-                        expected: "list or number".into(),
-                        received: other.to_string().into(),
-                    });
-                }
-            }
-        }
+    fold(
+        args,
+        "+",
+        |a, b| checked(a, "+", b, a.checked_add(b)),
+        |a, b| Ok(a + b),
+    )
+}
+
+fn sub(args: MalType) -> Result<MalType, ReplError> {
+    fold(
+        args,
+        "-",
+        |a, b| checked(a, "-", b, a.checked_sub(b)),
+        |a, b| Ok(a - b),
+    )
+}
+
+fn mult(args: MalType) -> Result<MalType, ReplError> {
+    fold(
+        args,
+        "*",
+        |a, b| checked(a, "*", b, a.checked_mul(b)),
+        |a, b| Ok(a * b),
+    )
+}
+
+fn div(args: MalType) -> Result<MalType, ReplError> {
+    fold(
+        args,
+        "/",
+        |a, b| match b {
+            0 => Err(ReplError::ZeroDivision),
+            b => checked(a, "/", b, a.checked_div(b)),
+        },
+        |a, b| match b {
+            0.0 => Err(ReplError::ZeroDivision),
+            b => Ok(a / b),
+        },
+    )
+}
+
+fn checked(a: i128, operation: &str, b: i128, result: Option<i128>) -> Result<i128, ReplError> {
+    result.ok_or_else(|| ReplError::Overflow(format!("{a} {operation} {b}").into()))
+}
+
+// Integers stay integers until a float takes part, after which the rest of the fold is done
+// in floating point.
+fn fold(
+    args: MalType,
+    name: &str,
+    int: fn(i128, i128) -> Result<i128, ReplError>,
+    float: fn(f64, f64) -> Result<f64, ReplError>,
+) -> Result<MalType, ReplError> {
+    let tokens = match args {
+        MalType::List { tokens } => tokens,
+        other => vector![other],
+    };
 
-        Ok(MalType::Number(accumulator))
+    let mut operands = tokens.into_iter();
+    let mut accumulator = match operands.next() {
+        Some(token) => operand(token)?,
+        None => return Err(ReplError::Arguments(name.into())),
+    };
+
+    for token in operands {
+        accumulator = match (accumulator, operand(token)?) {
+            (MalType::Number(a), MalType::Number(b)) => MalType::Number(int(a, b)?),
+            (a, b) => MalType::Float(float(as_float(a), as_float(b))?),
+        };
     }
 
-    match args {
-        MalType::Number(num) => Ok(MalType::Number(num)),
-        MalType::List { tokens } => Ok(add_list(tokens)?),
+    Ok(accumulator)
+}
+
+fn operand(token: MalType) -> Result<MalType, ReplError> {
+    let value = match token {
+        token @ MalType::List { .. } => parser::eval(&token)?,
+        MalType::Symbol(symbol) => match get_from_env(symbol) {
+            Some(value) => value,
+            None => return Err(ReplError::UnknownSymbol(symbol.as_str().into())),
+        },
+        other => other,
+    };
+
+    match value {
+        MalType::Number(_) | MalType::Float(_) => Ok(value),
         other => Err(ReplError::Type {
-            expected: "list or number".into(),
+            expected: "number".into(),
             received: other.to_string().into(),
         }),
     }
 }
 
-fn sub(args: MalType) -> Result<MalType, ReplError> {
-    fn sub_list(list: Vec<MalType>) -> Result<MalType, ReplError> {
-        let mut accumulator = None;
-
-        for token in list {
-            match token {
-                MalType::Number(num) => {
-                    accumulator = accumulator.map_or(Some(num), |a| Some(a - num))
-                }
-                MalType::List { tokens } => {
-                    let res = parser::eval(MalType::List { tokens })?;
-                    if let MalType::Number(num) = res {
-                        accumulator = accumulator.map_or(Some(num), |a| Some(a - num))
-                    }
-                }
-                MalType::Symbol(symbol) => {
-                    if let Some(value) = get_from_env(&symbol) {
-                        match value {
-                            MalType::Number(num) => {
-                                accumulator = accumulator.map_or(Some(num), |a| Some(a - num))
-                            }
-                            other => {
-                                return Err(ReplError::Type {
-                                    expected: "number".into(),
-                                    received: other.to_string().into(),
-                                });
-                            }
-                        }
-                    } else {
-                        return Err(ReplError::UnknownSymbol(symbol));
-                    }
-                }
-                other => {
-                    return Err(ReplError::Type {
-                        expected: "list or number".into(),
-                        received: other.to_string().into(),
-                    });
-                }
-            }
+fn as_float(number: MalType) -> f64 {
+    match number {
+        MalType::Number(num) => num as f64,
+        MalType::Float(num) => num,
+        _ => unreachable!("operands are always numbers"),
+    }
+}
+
+fn readline(args: MalType) -> Result<MalType, ReplError> {
+    let prompt = string_arg(args, "readline")?;
+
+    match repl::readline(&prompt)? {
+        Some(line) => Ok(MalType::string(&line)),
+        None => Ok(MalType::Nil),
+    }
+}
+
+fn load_file(args: MalType) -> Result<MalType, ReplError> {
+    let path = string_arg(args, "load-file")?;
+    parser::load_file(&path)?;
+    Ok(MalType::Nil)
+}
+
+fn set_reader_macro(args: MalType) -> Result<MalType, ReplError> {
+    let mut tokens = match args {
+        MalType::List { tokens } if tokens.len() == 2 => tokens,
+        _ => return Err(ReplError::Arguments("set-reader-macro!".into())),
+    };
+    let handler = tokens.pop_back().unwrap();
+    let prefix = string_arg(MalType::List { tokens }, "set-reader-macro!")?;
+
+    let reader_macro = match parser::eval(&handler)? {
+        MalType::String(symbol) => Some(ReaderMacro::Wrap(symbol.as_ref().into())),
+        MalType::Function { func, .. } => Some(ReaderMacro::Function(func)),
+        MalType::Nil => None,
+        other => {
+            return Err(ReplError::Type {
+                expected: "string, function or nil".into(),
+                received: other.to_string().into(),
+            });
         }
+    };
+
+    reader::set_macro(&prefix, reader_macro)?;
+    Ok(MalType::Nil)
+}
 
-        Ok(MalType::Number(accumulator.unwrap()))
+fn pprint(args: MalType) -> Result<MalType, ReplError> {
+    let tokens = match args {
+        MalType::List { tokens } if (1..=3).contains(&tokens.len()) => tokens,
+        _ => return Err(ReplError::Arguments("pprint".into())),
+    };
+    let mut tokens = tokens.iter().map(parser::eval);
+    let value = tokens.next().unwrap()?;
+
+    let mut options = Options::default();
+    match tokens.next().transpose()? {
+        Some(MalType::Number(width)) if width > 0 => options.width = width as usize,
+        Some(other) => {
+            return Err(ReplError::Type {
+                expected: "positive number".into(),
+                received: other.to_string().into(),
+            });
+        }
+        None => {}
     }
+    match tokens.next().transpose()? {
+        Some(MalType::Number(indent)) if indent >= 0 => options.indent = indent as usize,
+        Some(other) => {
+            return Err(ReplError::Type {
+                expected: "non-negative number".into(),
+                received: other.to_string().into(),
+            });
+        }
+        None => {}
+    }
+
+    println!("{}", pretty::pretty(&value, &options));
+    Ok(MalType::Nil)
+}
 
-    match args {
-        MalType::Number(num) => Ok(MalType::Number(num)),
-        MalType::List { tokens } => Ok(sub_list(tokens)?),
+fn atom(args: MalType) -> Result<MalType, ReplError> {
+    let [value] = eval_args(args, "atom")?;
+    Ok(MalType::Atom(Rc::new(RefCell::new(value))))
+}
+
+fn is_atom(args: MalType) -> Result<MalType, ReplError> {
+    let [value] = eval_args(args, "atom?")?;
+    Ok(MalType::Bool(matches!(value, MalType::Atom(_))))
+}
+
+fn deref(args: MalType) -> Result<MalType, ReplError> {
+    let [atom] = eval_args(args, "deref")?;
+    Ok(atom_arg(atom)?.borrow().clone())
+}
+
+fn reset(args: MalType) -> Result<MalType, ReplError> {
+    let [atom, value] = eval_args(args, "reset!")?;
+    atom_arg(atom)?.replace(value.clone());
+    Ok(value)
+}
+
+// (json-parse text) or (json-parse text keywordize?)
+fn json_parse(args: MalType) -> Result<MalType, ReplError> {
+    let (text, keywords) = value_and_flag(args, "json-parse")?;
+    match text {
+        MalType::String(text) => json::parse(&text, keywords),
         other => Err(ReplError::Type {
-            expected: "list or number".into(),
+            expected: "string".into(),
             received: other.to_string().into(),
         }),
     }
 }
 
-fn mult(args: MalType) -> Result<MalType, ReplError> {
-    fn mult_list(list: Vec<MalType>) -> Result<MalType, ReplError> {
-        if list.is_empty() {
-            return Err(ReplError::Arguments("*".into()));
-        }
-        let mut accumulator = 1;
-
-        for token in list {
-            match token {
-                MalType::Number(num) => accumulator *= num,
-                MalType::List { tokens } => {
-                    let res = parser::eval(MalType::List { tokens })?;
-                    if let MalType::Number(num) = res {
-                        accumulator *= num;
-                    }
-                }
-                MalType::Symbol(symbol) => {
-                    if let Some(value) = get_from_env(&symbol) {
-                        match value {
-                            MalType::Number(num) => accumulator *= num,
-                            other => {
-                                return Err(ReplError::Type {
-                                    expected: "number".into(),
-                                    received: other.to_string().into(),
-                                });
-                            }
-                        }
-                    } else {
-                        return Err(ReplError::UnknownSymbol(symbol));
-                    }
-                }
-                other => {
-                    return Err(ReplError::Type {
-                        expected: "list or number".into(),
-                        received: other.to_string().into(),
-                    });
-                }
-            }
+// (json-encode value) or (json-encode value pretty?)
+fn json_encode(args: MalType) -> Result<MalType, ReplError> {
+    let (value, pretty) = value_and_flag(args, "json-encode")?;
+    json::encode(&value, pretty).map(|json| MalType::string(&json))
+}
+
+fn read_edn(args: MalType) -> Result<MalType, ReplError> {
+    let text = string_arg(args, "read-edn")?;
+    edn::read(&text)
+}
+
+fn write_edn(args: MalType) -> Result<MalType, ReplError> {
+    let [value] = eval_args(args, "write-edn")?;
+    edn::write(&value).map(|edn| MalType::string(&edn))
+}
+
+// (serialize value path) writes the binary encoding of value to path.
+fn serialize(args: MalType) -> Result<MalType, ReplError> {
+    let [value, path] = eval_args(args, "serialize")?;
+    let MalType::String(path) = path else {
+        return Err(ReplError::Type {
+            expected: "string".into(),
+            received: path.to_string().into(),
+        });
+    };
+    fs::write(path.as_ref(), binary::serialize(&value)?)?;
+    Ok(MalType::Nil)
+}
+
+fn deserialize(args: MalType) -> Result<MalType, ReplError> {
+    let path = string_arg(args, "deserialize")?;
+    binary::deserialize(&fs::read(path.as_ref())?)
+}
+
+// (csv-read text), (csv-read text separator) or (csv-read text separator header?)
+fn csv_read(args: MalType) -> Result<MalType, ReplError> {
+    let tokens = match args {
+        MalType::List { tokens } if (1..=3).contains(&tokens.len()) => tokens,
+        _ => return Err(ReplError::Arguments("csv-read".into())),
+    };
+    let mut tokens = tokens.iter().map(parser::eval);
+    let text = match tokens.next().unwrap()? {
+        MalType::String(text) => text,
+        other => {
+            return Err(ReplError::Type {
+                expected: "string".into(),
+                received: other.to_string().into(),
+            });
         }
+    };
+    let separator = separator_arg(tokens.next().transpose()?)?;
+    let header = tokens
+        .next()
+        .transpose()?
+        .is_some_and(|header| !matches!(header, MalType::Nil | MalType::Bool(false)));
+    csv::read(&text, separator, header)
+}
 
-        Ok(MalType::Number(accumulator))
+// (csv-write rows) or (csv-write rows separator)
+fn csv_write(args: MalType) -> Result<MalType, ReplError> {
+    let tokens = match args {
+        MalType::List { tokens } if (1..=2).contains(&tokens.len()) => tokens,
+        _ => return Err(ReplError::Arguments("csv-write".into())),
+    };
+    let mut tokens = tokens.iter().map(parser::eval);
+    let rows = tokens.next().unwrap()?;
+    let separator = separator_arg(tokens.next().transpose()?)?;
+    csv::write(&rows, separator).map(|csv| MalType::string(&csv))
+}
+
+fn separator_arg(arg: Option<MalType>) -> Result<char, ReplError> {
+    let separator = match arg {
+        None | Some(MalType::Nil) => return Ok(','),
+        Some(MalType::String(separator)) => separator,
+        Some(other) => {
+            return Err(ReplError::Type {
+                expected: "single character string".into(),
+                received: other.to_string().into(),
+            });
+        }
+    };
+    let mut chars = separator.chars();
+    match (chars.next(), chars.next()) {
+        (Some(separator), None) if !matches!(separator, '"' | '\r' | '\n') => Ok(separator),
+        _ => Err(ReplError::Type {
+            expected: "single character string".into(),
+            received: parser::print_str(MalType::String(separator)).into(),
+        }),
     }
+}
+
+fn value_and_flag(args: MalType, name: &str) -> Result<(MalType, bool), ReplError> {
+    let tokens = match args {
+        MalType::List { tokens } if (1..=2).contains(&tokens.len()) => tokens,
+        _ => return Err(ReplError::Arguments(name.into())),
+    };
+    let mut tokens = tokens.iter().map(parser::eval);
+    let value = tokens.next().unwrap()?;
+    let flag = tokens
+        .next()
+        .transpose()?
+        .is_some_and(|flag| !matches!(flag, MalType::Nil | MalType::Bool(false)));
+    Ok((value, flag))
+}
 
-    match args {
-        MalType::Number(num) => Ok(MalType::Number(num)),
-        MalType::List { tokens } => Ok(mult_list(tokens)?),
+fn atom_arg(arg: MalType) -> Result<Rc<RefCell<MalType>>, ReplError> {
+    match arg {
+        MalType::Atom(atom) => Ok(atom),
         other => Err(ReplError::Type {
-            expected: "list or number".into(),
+            expected: "atom".into(),
             received: other.to_string().into(),
         }),
     }
 }
 
-fn div(args: MalType) -> Result<MalType, ReplError> {
-    fn div_list(list: Vec<MalType>) -> Result<MalType, ReplError> {
-        let mut accumulator = None;
-
-        for token in list {
-            match token {
-                MalType::Number(num) => {
-                    if num == 0 {
-                        return Err(ReplError::ZeroDivision);
-                    } else {
-                        accumulator = accumulator.map_or(Some(num), |a| Some(a / num))
-                    }
-                }
-                MalType::List { tokens } => {
-                    let res = parser::eval(MalType::List { tokens })?;
-                    if let MalType::Number(num) = res {
-                        if num == 0 {
-                            return Err(ReplError::ZeroDivision);
-                        } else {
-                            accumulator = accumulator.map_or(Some(num), |a| Some(a / num))
-                        }
-                    }
-                }
-                MalType::Symbol(symbol) => {
-                    if let Some(value) = get_from_env(&symbol) {
-                        match value {
-                            MalType::Number(num) => {
-                                accumulator = accumulator.map_or(Some(num), |a| Some(a / num))
-                            }
-                            other => {
-                                return Err(ReplError::Type {
-                                    expected: "number".into(),
-                                    received: other.to_string().into(),
-                                });
-                            }
-                        }
-                    } else {
-                        return Err(ReplError::UnknownSymbol(symbol));
-                    }
-                }
-                other => {
-                    return Err(ReplError::Type {
-                        expected: "list or number".into(),
-                        received: other.to_string().into(),
-                    });
-                }
-            }
-        }
-
-        Ok(MalType::Number(accumulator.unwrap()))
-    }
+fn eval_args<const N: usize>(args: MalType, name: &str) -> Result<[MalType; N], ReplError> {
+    let tokens = match args {
+        MalType::List { tokens } if tokens.len() == N => tokens,
+        _ => return Err(ReplError::Arguments(name.into())),
+    };
+    let tokens = tokens
+        .iter()
+        .map(parser::eval)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(tokens.try_into().unwrap())
+}
 
-    let args = {
-        if let MalType::List { tokens } = &args
-            && let Some(token) = tokens.first()
-            && let MalType::Symbol(_) = token
-        {
-            parser::eval(args)?
-        } else {
-            args
-        }
+fn string_arg(args: MalType, name: &str) -> Result<Rc<str>, ReplError> {
+    let arg = match args {
+        MalType::List { tokens } if !tokens.is_empty() => parser::eval(&tokens[0])?,
+        _ => return Err(ReplError::Arguments(name.into())),
     };
 
-    match args {
-        MalType::Number(num) => Ok(MalType::Number(num)),
-        MalType::List { tokens } => Ok(div_list(tokens)?),
+    match arg {
+        MalType::String(string) => Ok(string),
         other => Err(ReplError::Type {
-            expected: "list or number".into(),
+            expected: "string".into(),
             received: other.to_string().into(),
         }),
     }
 }
 
-fn get_from_env(key: &str) -> Option<MalType> {
+fn get_from_env(key: Symbol) -> Option<MalType> {
     ENV.with(|env| env.get(key))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(numbers: &[MalType]) -> MalType {
+        MalType::List {
+            tokens: numbers.iter().cloned().collect(),
+        }
+    }
+
+    #[test]
+    fn integer_arithmetic_stays_integral() {
+        let args = numbers(&[MalType::Number(7), MalType::Number(2)]);
+        assert_eq!(add(args.clone()).unwrap(), MalType::Number(9));
+        assert_eq!(sub(args.clone()).unwrap(), MalType::Number(5));
+        assert_eq!(mult(args.clone()).unwrap(), MalType::Number(14));
+        assert_eq!(div(args).unwrap(), MalType::Number(3));
+    }
+
+    #[test]
+    fn a_float_promotes_the_rest_of_the_fold() {
+        let args = numbers(&[MalType::Number(1), MalType::Float(0.5), MalType::Number(2)]);
+        assert_eq!(add(args.clone()).unwrap(), MalType::Float(3.5));
+        assert_eq!(div(args).unwrap(), MalType::Float(1.0));
+    }
+
+    #[test]
+    fn integer_overflow_is_an_error() {
+        let max = MalType::Number(i128::MAX);
+        let min = MalType::Number(i128::MIN);
+        assert!(matches!(
+            add(numbers(&[max.clone(), MalType::Number(1)])),
+            Err(ReplError::Overflow(_))
+        ));
+        assert!(matches!(
+            sub(numbers(&[min.clone(), MalType::Number(1)])),
+            Err(ReplError::Overflow(_))
+        ));
+        assert!(matches!(
+            mult(numbers(&[max, MalType::Number(2)])),
+            Err(ReplError::Overflow(_))
+        ));
+        assert!(matches!(
+            div(numbers(&[min, MalType::Number(-1)])),
+            Err(ReplError::Overflow(_))
+        ));
+    }
+
+    #[test]
+    fn dividing_by_zero_is_an_error() {
+        for args in [
+            [MalType::Number(1), MalType::Number(0)],
+            [MalType::Float(1.0), MalType::Float(0.0)],
+            [MalType::Number(1), MalType::Float(0.0)],
+        ] {
+            assert!(matches!(div(numbers(&args)), Err(ReplError::ZeroDivision)));
+        }
+    }
+
+    #[test]
+    fn arithmetic_needs_an_operand() {
+        assert!(matches!(add(numbers(&[])), Err(ReplError::Arguments(_))));
+    }
+}
@@ -5,6 +5,43 @@ pub enum ReplError {
     #[error("Unclosed '{0}'.")]
     Unclosed(char),
 
+    #[error("Invalid escape sequence '{0}' in string.")]
+    Escape(Box<str>),
+
+    #[error("Invalid number literal '{0}'.")]
+    Number(Box<str>),
+
+    #[error("Integer overflow: {0}.")]
+    Overflow(Box<str>),
+
+    #[error("Invalid reader dispatch: {0}.")]
+    Dispatch(Box<str>),
+
+    #[error("Invalid regex: {0}")]
+    Regex(Box<str>),
+
+    #[error("Conversion failed: {0}.")]
+    Conversion(Box<str>),
+
+    #[error("Invalid CSV at line {line}: {message}.")]
+    Csv { message: Box<str>, line: usize },
+
+    #[error("Invalid EDN: {0}.")]
+    Edn(Box<str>),
+
+    #[error("Invalid JSON at line {line}, column {column}: {message}.")]
+    Json {
+        message: Box<str>,
+        line: usize,
+        column: usize,
+    },
+
+    #[error("Unexpected '{0}'.")]
+    Unexpected(char),
+
+    #[error("Forms are nested deeper than the limit of {0}.")]
+    Depth(usize),
+
     #[error("Found EOF.")]
     Eof,
 
@@ -25,4 +62,37 @@ pub enum ReplError {
 
     #[error("Odd number of arguments passed for let*.")]
     OddLet,
+
+    #[error("Odd number of forms in hash-map literal.")]
+    OddMap,
+
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl ReplError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Unclosed(_) => "unclosed",
+            Self::Escape(_) => "escape",
+            Self::Number(_) => "number",
+            Self::Overflow(_) => "overflow",
+            Self::Dispatch(_) => "dispatch",
+            Self::Regex(_) => "regex",
+            Self::Conversion(_) => "conversion",
+            Self::Csv { .. } => "csv",
+            Self::Edn(_) => "edn",
+            Self::Json { .. } => "json",
+            Self::Unexpected(_) => "unexpected",
+            Self::Depth(_) => "depth",
+            Self::Eof => "eof",
+            Self::Arguments(_) => "arguments",
+            Self::Type { .. } => "type",
+            Self::UnknownSymbol(_) => "unknown-symbol",
+            Self::ZeroDivision => "zero-division",
+            Self::OddLet => "odd-let",
+            Self::OddMap => "odd-map",
+            Self::Io(_) => "io",
+        }
+    }
 }
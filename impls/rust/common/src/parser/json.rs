@@ -0,0 +1,472 @@
+use crate::parser::errors::ReplError;
+use crate::parser::reader;
+use crate::parser::types::{Entries, MalType, Tokens};
+
+const INDENT: &str = "  ";
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    depth: usize,
+    keywords: bool,
+}
+
+// Objects become hash-maps keyed by strings, or by keywords when `keywords` is set.
+pub fn parse(src: &str, keywords: bool) -> Result<MalType, ReplError> {
+    let mut parser = Parser {
+        src,
+        pos: 0,
+        depth: 0,
+        keywords,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < src.len() {
+        return Err(parser.error("trailing characters after value"));
+    }
+    Ok(value)
+}
+
+pub fn encode(value: &MalType, pretty: bool) -> Result<String, ReplError> {
+    let mut out = String::new();
+    write_value(&mut out, value, pretty.then_some(0))?;
+    Ok(out)
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> ReplError {
+        let before = &self.src[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        ReplError::Json {
+            message: message.into(),
+            line,
+            column,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.pos += char.len_utf8();
+        Some(char)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ReplError> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{expected}'")))
+        }
+    }
+
+    fn value(&mut self) -> Result<MalType, ReplError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(open @ ('{' | '[')) => {
+                if self.depth >= reader::max_depth() {
+                    return Err(ReplError::Depth(reader::max_depth()));
+                }
+                self.depth += 1;
+                let value = if open == '{' {
+                    self.object()
+                } else {
+                    self.array()
+                };
+                self.depth -= 1;
+                value
+            }
+            Some('"') => self.string().map(|string| MalType::String(string.into())),
+            Some('-' | '0'..='9') => self.number(),
+            Some(_) => self.literal(),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn literal(&mut self) -> Result<MalType, ReplError> {
+        for (literal, value) in [
+            ("true", MalType::Bool(true)),
+            ("false", MalType::Bool(false)),
+            ("null", MalType::Nil),
+        ] {
+            if self.src[self.pos..].starts_with(literal) {
+                self.pos += literal.len();
+                return Ok(value);
+            }
+        }
+        Err(self.error("expected value"))
+    }
+
+    fn object(&mut self) -> Result<MalType, ReplError> {
+        self.pos += 1;
+        // A repeated key replaces the earlier value.
+        let mut entries = Entries::default();
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(MalType::HashMap { entries });
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected string key"));
+            }
+            let name = self.string()?;
+            self.expect(':')?;
+            let value = self.value()?;
+
+            let key = if self.keywords {
+                MalType::Symbol(format!(":{name}").into())
+            } else {
+                MalType::String(name.as_ref().into())
+            };
+            entries.insert(key, value);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(MalType::HashMap { entries });
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<MalType, ReplError> {
+        self.pos += 1;
+        let mut tokens = Tokens::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(MalType::Vector { tokens });
+        }
+
+        loop {
+            tokens.push_back(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(MalType::Vector { tokens });
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<Box<str>, ReplError> {
+        self.pos += 1;
+        let mut string = String::new();
+
+        loop {
+            let start = self.pos;
+            match self.next() {
+                Some('"') => return Ok(string.into()),
+                Some('\\') => {
+                    let char = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape(start)?,
+                        _ => {
+                            self.pos = start;
+                            return Err(self.error("invalid escape sequence"));
+                        }
+                    };
+                    string.push(char);
+                }
+                Some(char) if char.is_control() => {
+                    self.pos = start;
+                    return Err(self.error("control character in string"));
+                }
+                Some(char) => string.push(char),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self, start: usize) -> Result<char, ReplError> {
+        let high = self.hex4(start)?;
+        let code = match high {
+            0xd800..=0xdbff if self.src[self.pos..].starts_with("\\u") => {
+                self.pos += 2;
+                let low = self.hex4(start)?;
+                if !(0xdc00..=0xdfff).contains(&low) {
+                    self.pos = start;
+                    return Err(self.error("invalid surrogate pair"));
+                }
+                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+            }
+            code => code,
+        };
+        char::from_u32(code).ok_or_else(|| {
+            self.pos = start;
+            self.error("invalid unicode escape")
+        })
+    }
+
+    fn hex4(&mut self, start: usize) -> Result<u32, ReplError> {
+        let digits = self.src.get(self.pos..self.pos + 4);
+        match digits.and_then(|digits| u32::from_str_radix(digits, 16).ok()) {
+            Some(code) if digits.is_some_and(|digits| !digits.starts_with('+')) => {
+                self.pos += 4;
+                Ok(code)
+            }
+            _ => {
+                self.pos = start;
+                Err(self.error("invalid unicode escape"))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<MalType, ReplError> {
+        let start = self.pos;
+        let rest = &self.src[start..];
+        let bytes = rest.as_bytes();
+
+        let mut len = usize::from(bytes[0] == b'-');
+        let digits = |from: usize| {
+            bytes[from..]
+                .iter()
+                .take_while(|byte| byte.is_ascii_digit())
+                .count()
+        };
+
+        let integer = digits(len);
+        if integer == 0 || (integer > 1 && bytes[len] == b'0') {
+            return Err(self.error("invalid number"));
+        }
+        len += integer;
+
+        let mut float = false;
+        if bytes.get(len) == Some(&b'.') {
+            let fraction = digits(len + 1);
+            if fraction == 0 {
+                self.pos += len + 1;
+                return Err(self.error("expected digit after '.'"));
+            }
+            len += 1 + fraction;
+            float = true;
+        }
+        if let Some(b'e' | b'E') = bytes.get(len) {
+            len += 1;
+            if let Some(b'+' | b'-') = bytes.get(len) {
+                len += 1;
+            }
+            let exponent = digits(len);
+            if exponent == 0 {
+                self.pos += len;
+                return Err(self.error("expected digit in exponent"));
+            }
+            len += exponent;
+            float = true;
+        }
+
+        let literal = &rest[..len];
+        self.pos += len;
+        match literal.parse::<i128>() {
+            Ok(number) if !float => Ok(MalType::Number(number)),
+            _ => Ok(MalType::Float(literal.parse().unwrap())),
+        }
+    }
+}
+
+fn write_value(out: &mut String, value: &MalType, depth: Option<usize>) -> Result<(), ReplError> {
+    match value {
+        MalType::Nil => out.push_str("null"),
+        MalType::Bool(boolean) => out.push_str(&boolean.to_string()),
+        MalType::Number(number) => out.push_str(&number.to_string()),
+        MalType::Float(number) if number.is_finite() => out.push_str(&format!("{number:?}")),
+        MalType::String(string) => write_string(out, string),
+        // Keywords become strings without their colon, the same as keyword keys.
+        MalType::Symbol(keyword) if keyword.starts_with(':') => write_string(out, &keyword[1..]),
+        MalType::List { tokens } | MalType::Vector { tokens } => {
+            write_collection(out, '[', ']', tokens.iter(), depth, |out, token, depth| {
+                write_value(out, token, depth)
+            })?
+        }
+        MalType::Set { members } => {
+            write_collection(out, '[', ']', members.iter(), depth, write_value)?
+        }
+        MalType::HashMap { entries } => {
+            let separator = if depth.is_some() { ": " } else { ":" };
+            write_collection(
+                out,
+                '{',
+                '}',
+                entries.iter(),
+                depth,
+                |out, (key, value), depth| {
+                    let key = match key {
+                        MalType::String(key) => key,
+                        MalType::Symbol(key) if key.starts_with(':') => &key[1..],
+                        other => {
+                            return Err(ReplError::Type {
+                                expected: "string or keyword key".into(),
+                                received: other.to_string().into(),
+                            });
+                        }
+                    };
+                    write_string(out, key);
+                    out.push_str(separator);
+                    write_value(out, value, depth)
+                },
+            )?
+        }
+        other => {
+            return Err(ReplError::Type {
+                expected: "JSON value".into(),
+                received: other.to_string().into(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn write_collection<T>(
+    out: &mut String,
+    open: char,
+    close: char,
+    items: impl Iterator<Item = T>,
+    depth: Option<usize>,
+    write: impl Fn(&mut String, T, Option<usize>) -> Result<(), ReplError>,
+) -> Result<(), ReplError> {
+    out.push(open);
+    let mut empty = true;
+    let inner = depth.map(|depth| depth + 1);
+
+    for item in items {
+        if !empty {
+            out.push(',');
+        }
+        if let Some(inner) = inner {
+            out.push('\n');
+            out.push_str(&INDENT.repeat(inner));
+        }
+        write(out, item, inner)?;
+        empty = false;
+    }
+
+    if let Some(depth) = depth
+        && !empty
+    {
+        out.push('\n');
+        out.push_str(&INDENT.repeat(depth));
+    }
+    out.push(close);
+    Ok(())
+}
+
+fn write_string(out: &mut String, string: &str) {
+    out.push('"');
+    for char in string.chars() {
+        match char {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            char if (char as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", char as u32)),
+            char => out.push(char),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::edn;
+
+    fn position(src: &str) -> (usize, usize) {
+        match parse(src, false) {
+            Err(ReplError::Json { line, column, .. }) => (line, column),
+            other => panic!("expected a JSON error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        assert_eq!(position(""), (1, 1));
+        assert_eq!(position("[1, 2,]"), (1, 7));
+        assert_eq!(position("{\n  \"a\": 1,\n  b: 2\n}"), (3, 3));
+        assert_eq!(position("[1]\n  x"), (2, 3));
+        assert_eq!(position("\"é\" x"), (1, 5));
+    }
+
+    #[test]
+    fn later_duplicate_keys_replace_earlier_ones() {
+        let value = parse(r#"{"a": 1, "b": 2, "a": 3}"#, false).unwrap();
+        assert_eq!(value, parse(r#"{"b": 2, "a": 3}"#, false).unwrap());
+
+        let value = parse(r#"{"a": 1, "a": {"a": 2}}"#, true).unwrap();
+        assert_eq!(encode(&value, false).unwrap(), r#"{"a":{"a":2}}"#);
+    }
+
+    #[test]
+    fn keywordizes_keys_on_request() {
+        let value = parse(r#"{"a": null}"#, true).unwrap();
+        let MalType::HashMap { entries } = value else {
+            panic!("expected a hash-map");
+        };
+        assert_eq!(
+            entries.get(&MalType::Symbol(":a".into())),
+            Some(&MalType::Nil)
+        );
+    }
+
+    #[test]
+    fn round_trips_values() {
+        let src = r#"[{"s":"a\"\\\né"},-12,1.5,true,[null,[],{}]]"#;
+        assert_eq!(encode(&parse(src, false).unwrap(), false).unwrap(), src);
+
+        let value = parse(r#"{"f": 1.5, "l": [null], "n": -12, "t": true}"#, true).unwrap();
+        assert_eq!(parse(&encode(&value, false).unwrap(), true).unwrap(), value);
+        assert_eq!(
+            encode(&parse("[1,{\"a\":[]}]", false).unwrap(), true).unwrap(),
+            "[\n  1,\n  {\n    \"a\": []\n  }\n]"
+        );
+    }
+
+    #[test]
+    fn encodes_keyword_values_as_strings() {
+        let value = edn::read("{:k :v :l [:a :b/c]}").unwrap();
+        let encoded = encode(&value, false).unwrap();
+        assert_eq!(
+            parse(&encoded, false).unwrap(),
+            parse(r#"{"k": "v", "l": ["a", "b/c"]}"#, false).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_values_json_cannot_hold() {
+        assert!(encode(&MalType::Float(f64::NAN), false).is_err());
+        assert!(encode(&MalType::Symbol("sym".into()), false).is_err());
+        let map = MalType::HashMap {
+            entries: [(MalType::Number(1), MalType::Nil)].into_iter().collect(),
+        };
+        assert!(encode(&map, false).is_err());
+    }
+}
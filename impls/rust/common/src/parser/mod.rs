@@ -1,30 +1,203 @@
 use crate::parser::env::Env;
 use crate::parser::errors::ReplError;
-use crate::parser::types::{MalCollection, MalType};
-use std::mem;
+use crate::parser::pretty::Options;
+use crate::parser::symbol::Symbol;
+use crate::parser::types::MalCollection;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fs,
+    rc::Rc,
+    sync::LazyLock,
+};
 
+pub mod binary;
+#[cfg(feature = "serde")]
+pub mod convert;
+pub mod cst;
+pub mod csv;
+pub mod edn;
 mod env;
 pub mod errors;
+mod json;
+pub mod pretty;
 mod reader;
+pub mod symbol;
 mod types;
 
+pub use crate::parser::reader::{DEFAULT_MAX_DEPTH, TokenKind, classify, set_max_depth};
+pub use crate::parser::types::{Entries, MalType, Members, Tokens};
+
 thread_local! {
     pub static ENV: Env = Env::new();
+    static FILES: RefCell<HashMap<Box<str>, Box<str>>> = RefCell::new(HashMap::new());
+    static PRINT_WIDTH: Cell<Option<usize>> = const { Cell::new(None) };
+    static EVAL_DEPTH: Cell<usize> = const { Cell::new(0) };
+    static PRELUDE_LOADED: Cell<bool> = const { Cell::new(false) };
 }
 
+const PRELUDE: &str = include_str!("prelude.mal");
+
+pub const SPECIAL_FORMS: &[&str] = &["def!", "let*"];
+
+// Names eval looks at on every form, interned once so they are compared by handle.
+static DEF: LazyLock<Symbol> = LazyLock::new(|| SPECIAL_FORMS[0].into());
+static LET: LazyLock<Symbol> = LazyLock::new(|| SPECIAL_FORMS[1].into());
+static DEBUG_EVAL: LazyLock<Symbol> = LazyLock::new(|| "DEBUG-EVAL".into());
+
 pub fn rep(arg: &str) -> Result<Box<str>, ReplError> {
     let res = read(arg);
-    let res = eval(res?)?;
+    let res = eval(&res?)?;
+    Ok(print(res))
+}
+
+pub fn rep_with_history(arg: &str) -> Result<Box<str>, ReplError> {
+    let res = read(arg).and_then(|form| eval(&form));
+    ENV.with(|env| match &res {
+        Ok(value) => {
+            let second = env.get("*1".into()).unwrap_or_default();
+            let third = env.get("*2".into()).unwrap_or_default();
+            env.set_global("*3".into(), third);
+            env.set_global("*2".into(), second);
+            env.set_global("*1".into(), value.clone());
+        }
+        Err(ReplError::Eof) => {}
+        Err(e) => env.set_global("*e".into(), e.into()),
+    });
+    Ok(print(res?))
+}
+
+pub fn symbols() -> Vec<Box<str>> {
+    let mut symbols: Vec<Box<str>> =
+        ENV.with(|env| env.keys().iter().map(|key| key.as_str().into()).collect());
+    symbols.extend(SPECIAL_FORMS.iter().map(|&form| form.into()));
+    symbols.sort();
+    symbols.dedup();
+    symbols
+}
+
+pub fn bindings() -> Vec<(Box<str>, Box<str>)> {
+    ENV.with(|env| {
+        let mut keys = env.keys();
+        keys.sort_by_key(|key| key.as_str());
+        keys.dedup();
+        keys.into_iter()
+            .filter_map(|key| {
+                let value = print_str(env.get(key)?);
+                Some((key.as_str().into(), value.into()))
+            })
+            .collect()
+    })
+}
+
+pub fn eval_string(src: &str) -> Result<Box<str>, ReplError> {
+    let mut res = MalType::Nil;
+    for form in reader::read_all(src)? {
+        res = eval(&form)?;
+    }
     Ok(print(res))
 }
 
+pub fn load_file(path: &str) -> Result<(), ReplError> {
+    let bundled = FILES.with(|files| files.borrow().get(path).cloned());
+    match bundled {
+        Some(contents) => eval_string(&contents)?,
+        None => eval_string(&fs::read_to_string(path)?)?,
+    };
+    Ok(())
+}
+
+pub fn register_file(path: &str, contents: &str) {
+    FILES.with(|files| files.borrow_mut().insert(path.into(), contents.into()));
+}
+
+pub fn loaded_files(src: &str) -> Result<Vec<Box<str>>, ReplError> {
+    fn collect(form: &MalType, paths: &mut Vec<Box<str>>) {
+        match form {
+            MalType::List { tokens } => {
+                if let (2, Some(MalType::Symbol(head)), Some(MalType::String(path))) =
+                    (tokens.len(), tokens.front(), tokens.get(1))
+                    && head.as_ref() == "load-file"
+                {
+                    paths.push(path.as_ref().into());
+                }
+                tokens.iter().for_each(|token| collect(token, paths));
+            }
+            MalType::Vector { tokens } => tokens.iter().for_each(|token| collect(token, paths)),
+            MalType::HashMap { entries } => entries.iter().for_each(|(key, value)| {
+                collect(key, paths);
+                collect(value, paths);
+            }),
+            MalType::Set { members } => members.iter().for_each(|member| collect(member, paths)),
+            _ => {}
+        }
+    }
+
+    let mut paths = Vec::new();
+    for form in reader::read_all(src)? {
+        collect(&form, &mut paths);
+    }
+    Ok(paths)
+}
+
+pub fn load_prelude() -> Result<(), ReplError> {
+    eval_string(PRELUDE)?;
+    PRELUDE_LOADED.with(|loaded| loaded.set(true));
+    Ok(())
+}
+
+pub fn set_argv(args: &[String]) {
+    let tokens = args.iter().map(|arg| MalType::string(arg)).collect();
+    ENV.with(|env| env.set_global("*ARGV*".into(), MalType::List { tokens }));
+}
+
+// The prelude is evaluated again if it had been loaded, so a reset environment matches the one
+// the interpreter started with.
+pub fn reset() -> Result<(), ReplError> {
+    ENV.with(|env| env.reset());
+    reader::reset_macros();
+    if PRELUDE_LOADED.with(Cell::get) {
+        load_prelude()?;
+    }
+    Ok(())
+}
+
+// Results are pretty printed to this width instead of on a single line.
+pub fn set_print_width(width: Option<usize>) {
+    PRINT_WIDTH.with(|print_width| print_width.set(width));
+}
+
+pub fn toggle_debug() -> bool {
+    ENV.with(|env| {
+        let enabled = env
+            .get(*DEBUG_EVAL)
+            .is_some_and(|debug| !matches!(debug, MalType::Nil | MalType::Bool(false)));
+        env.set(*DEBUG_EVAL, MalType::Bool(!enabled));
+        !enabled
+    })
+}
+
 fn read(arg: &str) -> Result<MalType, ReplError> {
     reader::read_string(arg)
 }
 
-fn eval(arg: MalType) -> Result<MalType, ReplError> {
+// Forms are only borrowed, so the same form can be evaluated any number of times. Evaluation
+// recurses into nested forms, so it stops at the same depth limit as the reader.
+fn eval(arg: &MalType) -> Result<MalType, ReplError> {
+    let max_depth = reader::max_depth();
+    let depth = EVAL_DEPTH.with(Cell::get);
+    if depth >= max_depth {
+        return Err(ReplError::Depth(max_depth));
+    }
+    EVAL_DEPTH.with(|eval_depth| eval_depth.set(depth + 1));
+    let value = eval_nested(arg);
+    EVAL_DEPTH.with(|eval_depth| eval_depth.set(depth));
+    value
+}
+
+fn eval_nested(arg: &MalType) -> Result<MalType, ReplError> {
     ENV.with(|env| {
-        if let Some(debug) = env.get("DEBUG-EVAL")
+        if let Some(debug) = env.get(*DEBUG_EVAL)
             && !matches!(debug, MalType::Nil)
             && !matches!(debug, MalType::Bool(false))
         {
@@ -32,46 +205,41 @@ fn eval(arg: MalType) -> Result<MalType, ReplError> {
         }
 
         match arg {
-            MalType::List { mut tokens } => {
-                if tokens.is_empty() {
-                    return Ok(MalType::List { tokens });
-                }
-                match mem::take(&mut tokens[0]) {
-                    MalType::Symbol(symbol) => match symbol.as_ref() {
-                        "def!" => {
+            MalType::List { tokens } => {
+                let Some(head) = tokens.front() else {
+                    return Ok(arg.clone());
+                };
+                match head {
+                    MalType::Symbol(symbol) => match *symbol {
+                        symbol if symbol == *DEF => {
                             if tokens.len() < 3 {
                                 return Err(ReplError::Arguments("def!".into()));
                             }
 
-                            let key = {
-                                match mem::take(&mut tokens[1]) {
-                                    MalType::Symbol(key) => key,
-                                    other => {
-                                        return Err(ReplError::Type {
-                                            expected: "symbol".into(),
-                                            received: other.to_string().into(),
-                                        });
-                                    }
+                            let key = match &tokens[1] {
+                                MalType::Symbol(key) => *key,
+                                other => {
+                                    return Err(ReplError::Type {
+                                        expected: "symbol".into(),
+                                        received: other.to_string().into(),
+                                    });
                                 }
                             };
-                            let retval = eval(mem::take(&mut tokens[2]))?;
-                            env.set(key, eval(retval.clone())?);
+                            let retval = eval(&tokens[2])?;
+                            env.set(key, retval.clone());
                             Ok(retval)
                         }
-                        "let*" => {
+                        symbol if symbol == *LET => {
                             if tokens.len() < 3 {
                                 return Err(ReplError::Arguments("let*".into()));
                             }
-                            env.new_env();
-                            let mut pairs = {
-                                match mem::take(&mut tokens[1]) {
-                                    MalType::List { tokens } | MalType::Vector { tokens } => tokens,
-                                    other => {
-                                        return Err(ReplError::Type {
-                                            expected: "list or vector".into(),
-                                            received: other.to_string().into(),
-                                        });
-                                    }
+                            let pairs = match &tokens[1] {
+                                MalType::List { tokens } | MalType::Vector { tokens } => tokens,
+                                other => {
+                                    return Err(ReplError::Type {
+                                        expected: "list or vector".into(),
+                                        received: other.to_string().into(),
+                                    });
                                 }
                             };
 
@@ -79,37 +247,22 @@ fn eval(arg: MalType) -> Result<MalType, ReplError> {
                                 return Err(ReplError::OddLet);
                             }
 
-                            for i in (0..pairs.len()).step_by(2) {
-                                let key = mem::take(&mut pairs[i]);
-                                let value = mem::take(&mut pairs[i + 1]);
-
-                                let key = match key {
-                                    MalType::Symbol(symbol) => symbol,
-                                    other => {
-                                        return Err(ReplError::Type {
-                                            expected: "symbol".into(),
-                                            received: other.to_string().into(),
-                                        });
-                                    }
-                                };
-
-                                env.set(key, eval(value)?);
-                            }
-
-                            let retval = eval(mem::take(&mut tokens[2]));
-
+                            env.new_env();
+                            let retval = eval_let(env, pairs, &tokens[2]);
                             env.pop_env();
 
                             retval
                         }
-                        _ => {
-                            let val = match env.get(&symbol) {
+                        symbol => {
+                            let val = match env.get(symbol) {
                                 Some(val) => val,
-                                None => return Err(ReplError::UnknownSymbol(symbol)),
+                                None => {
+                                    return Err(ReplError::UnknownSymbol(symbol.as_str().into()));
+                                }
                             };
                             match val {
-                                MalType::Function(func) => func(MalType::List {
-                                    tokens: tokens[1..].into(),
+                                MalType::Function { func, .. } => func(MalType::List {
+                                    tokens: tokens.skip(1),
                                 }),
                                 _ => Ok(val),
                             }
@@ -122,63 +275,195 @@ fn eval(arg: MalType) -> Result<MalType, ReplError> {
                 }
             }
 
-            MalType::Vector { mut tokens } => {
-                for token in &mut tokens {
-                    let cur = mem::take(token);
-                    *token = eval(cur)?
-                }
-
-                Ok(MalType::Vector { tokens })
-            }
+            MalType::Vector { tokens } => Ok(MalType::Vector {
+                tokens: tokens.iter().map(eval).collect::<Result<_, _>>()?,
+            }),
 
-            MalType::HashMap { mut tokens } => {
-                for token in &mut tokens {
-                    let cur = mem::take(token);
-                    *token = eval(cur)?
-                }
+            MalType::HashMap { entries } => Ok(MalType::HashMap {
+                entries: entries
+                    .iter()
+                    .map(|(key, value)| Ok((eval(key)?, eval(value)?)))
+                    .collect::<Result<_, ReplError>>()?,
+            }),
 
-                Ok(MalType::HashMap { tokens })
-            }
-            MalType::Symbol(symbol) => match env.get(&symbol) {
+            MalType::Set { members } => Ok(MalType::Set {
+                members: members.iter().map(eval).collect::<Result<_, _>>()?,
+            }),
+            MalType::Symbol(symbol) => match env.get(*symbol) {
                 Some(val) => Ok(val),
-                None => Err(ReplError::UnknownSymbol(symbol)),
+                None => Err(ReplError::UnknownSymbol(symbol.as_str().into())),
             },
-            _ => Ok(arg),
+            _ => Ok(arg.clone()),
         }
     })
 }
 
+fn eval_let(env: &Env, pairs: &Tokens, body: &MalType) -> Result<MalType, ReplError> {
+    for (key, value) in types::pairs(pairs) {
+        let key = match key {
+            MalType::Symbol(symbol) => *symbol,
+            other => {
+                return Err(ReplError::Type {
+                    expected: "symbol".into(),
+                    received: other.to_string().into(),
+                });
+            }
+        };
+
+        env.set(key, eval(value)?);
+    }
+
+    eval(body)
+}
+
 fn print(arg: MalType) -> Box<str> {
-    let mut ret = print_str(arg);
+    let mut ret = match PRINT_WIDTH.with(Cell::get) {
+        Some(width) => pretty::pretty(
+            &arg,
+            &Options {
+                width,
+                ..Default::default()
+            },
+        ),
+        None => print_str(arg),
+    };
     ret.push('\n');
     ret.into()
 }
 
 fn print_str(token: MalType) -> String {
-    fn make_collection(tokens: Vec<MalType>, start: char, end: char) -> String {
+    print_value(token, &mut Vec::new(), 0)
+}
+
+// Atoms currently being printed are kept in `atoms`, so an atom that ends up containing itself
+// is printed as `(atom ...)` the second time it is reached instead of recursing forever. Values
+// built by evaluation can nest deeper than the reader allows, so anything past the depth limit
+// is printed as `...`.
+fn print_value(token: MalType, atoms: &mut Vec<Rc<RefCell<MalType>>>, depth: usize) -> String {
+    fn make_collection(
+        tokens: impl IntoIterator<Item = MalType>,
+        start: &str,
+        end: char,
+        atoms: &mut Vec<Rc<RefCell<MalType>>>,
+        depth: usize,
+    ) -> String {
         let mut str = Vec::new();
         for tkn in tokens {
-            let stringified = print_str(tkn);
+            let stringified = print_value(tkn, atoms, depth + 1);
             if !stringified.is_empty() {
                 str.push(stringified);
             }
         }
         let str = str.join(" ");
         let mut ret = String::with_capacity(str.len() + 2);
-        ret.push(start);
+        ret.push_str(start);
         ret.push_str(&str);
         ret.push(end);
         ret
     }
 
+    if depth >= reader::max_depth() {
+        return "...".to_string();
+    }
+
     match token {
-        MalType::Symbol(name) | MalType::String(name) => name.into(),
+        MalType::Symbol(name) => name.to_string(),
+        MalType::String(string) => escape(&string),
         MalType::Number(num) => num.to_string(),
+        MalType::Float(num) => format!("{num:?}"),
         MalType::Bool(boolean) => boolean.to_string(),
         MalType::Nil => "nil".to_string(),
-        MalType::List { tokens } => make_collection(tokens, '(', ')'),
-        MalType::Vector { tokens } => make_collection(tokens, '[', ']'),
-        MalType::HashMap { tokens } => make_collection(tokens, '{', '}'),
-        MalType::Function(_) => panic!("Function is not a valid repl print type"),
+        MalType::List { tokens } => make_collection(tokens, "(", ')', atoms, depth),
+        MalType::Vector { tokens } => make_collection(tokens, "[", ']', atoms, depth),
+        MalType::HashMap { entries } => make_collection(
+            entries.into_iter().flat_map(|(key, value)| [key, value]),
+            "{",
+            '}',
+            atoms,
+            depth,
+        ),
+        MalType::Set { members } => make_collection(members, "#{", '}', atoms, depth),
+        MalType::Regex(regex) => format!("#\"{}\"", regex.as_str().replace('"', "\\\"")),
+        MalType::Function { name, .. } => format!("#<builtin {name}>"),
+        MalType::Atom(atom) if atoms.iter().any(|seen| Rc::ptr_eq(seen, &atom)) => {
+            "(atom ...)".to_string()
+        }
+        MalType::Atom(atom) => {
+            let value = atom.borrow().clone();
+            atoms.push(atom);
+            let ret = format!("(atom {})", print_value(value, atoms, depth + 1));
+            atoms.pop();
+            ret
+        }
+    }
+}
+
+fn escape(string: &str) -> String {
+    let mut ret = String::with_capacity(string.len() + 2);
+    ret.push('"');
+    for char in string.chars() {
+        match char {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\t' => ret.push_str("\\t"),
+            '\r' => ret.push_str("\\r"),
+            '\0' => ret.push_str("\\0"),
+            char if char.is_control() => ret.push_str(&format!("\\u{{{:x}}}", char as u32)),
+            char => ret.push(char),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn nested(open: &str, close: &str, depth: usize) -> String {
+        format!("{}1{}", open.repeat(depth), close.repeat(depth))
+    }
+
+    // Test threads get a 2 MiB stack by default, so this runs on one the size of the main thread's.
+    #[test]
+    fn reset_keeps_the_prelude_it_was_started_with() {
+        reset().unwrap();
+        assert!(rep("*host-language*").is_err());
+
+        load_prelude().unwrap();
+        rep("(def! x 1)").unwrap();
+        reset().unwrap();
+        assert_eq!(&*rep("*host-language*").unwrap(), "\"rust\"\n");
+        assert!(matches!(rep("x"), Err(ReplError::UnknownSymbol(_))));
+    }
+
+    #[test]
+    fn evaluates_and_prints_forms_nested_to_the_limit() {
+        thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(|| {
+                let depth = reader::max_depth();
+
+                let vectors = nested("[", "]", depth - 1);
+                assert_eq!(&*rep(&vectors).unwrap(), format!("{vectors}\n"));
+                let sums = nested("(+ 1 ", ")", depth - 1);
+                assert_eq!(&*rep(&sums).unwrap(), format!("{depth}\n"));
+
+                let deeper = nested("[", "]", depth);
+                assert!(matches!(rep(&deeper), Err(ReplError::Depth(max)) if max == depth));
+                let deeper = nested("(+ 1 ", ")", depth);
+                assert!(matches!(rep(&deeper), Err(ReplError::Depth(max)) if max == depth));
+
+                let value = (0..depth).fold(MalType::Number(1), |value, _| MalType::Vector {
+                    tokens: im_rc::vector![value],
+                });
+                let printed = format!("{}...{}", "[".repeat(depth), "]".repeat(depth));
+                assert_eq!(print_str(value), printed);
+            })
+            .unwrap()
+            .join()
+            .unwrap();
     }
 }
@@ -0,0 +1,229 @@
+use crate::parser::print_str;
+use crate::parser::reader;
+use crate::parser::types::MalType;
+
+pub struct Options {
+    pub width: usize,
+    pub indent: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            width: 80,
+            indent: 1,
+        }
+    }
+}
+
+// A document in the style of Wadler's "A prettier printer": every line break inside a group is
+// either rendered as a space, when the whole group fits on the current line, or as a newline.
+enum Doc {
+    Text(String),
+    Line,
+    Nest(usize, Box<Doc>),
+    Align(Box<Doc>),
+    // Atoms separated by spaces, breaking only before those that would run past the width.
+    Fill(Vec<String>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+type Frame<'a> = (usize, Mode, &'a Doc);
+
+pub fn pretty(value: &MalType, options: &Options) -> String {
+    render(&doc(value, options, 0), options.width)
+}
+
+fn doc(value: &MalType, options: &Options, depth: usize) -> Doc {
+    // As in print_str, values nested past the depth limit are elided.
+    if depth >= reader::max_depth() {
+        return Doc::Text("...".into());
+    }
+
+    let (open, elements, close): (_, Vec<&MalType>, _) = match value {
+        MalType::List { tokens } => ("(", tokens.iter().collect(), ")"),
+        MalType::Vector { tokens } => ("[", tokens.iter().collect(), "]"),
+        MalType::HashMap { .. } => ("{", Vec::new(), "}"),
+        MalType::Set { members } => ("#{", members.iter().collect(), "}"),
+        value => return Doc::Text(print_str(value.clone())),
+    };
+
+    let nest = options.indent;
+    let items: Vec<Doc> = match value {
+        MalType::HashMap { entries } => entries
+            .iter()
+            .map(|(key, value)| {
+                Doc::Concat(vec![
+                    doc(key, options, depth + 1),
+                    Doc::Text(" ".into()),
+                    doc(value, options, depth + 1),
+                ])
+            })
+            .collect(),
+        _ if elements.iter().all(|element| is_atom(element)) => {
+            let atoms = elements
+                .into_iter()
+                .map(|element| print_str(element.clone()))
+                .collect();
+            return Doc::Align(Box::new(Doc::Concat(vec![
+                Doc::Text(open.into()),
+                Doc::Nest(nest, Box::new(Doc::Fill(atoms))),
+                Doc::Text(close.into()),
+            ])));
+        }
+        _ => elements
+            .into_iter()
+            .map(|element| doc(element, options, depth + 1))
+            .collect(),
+    };
+
+    let mut body = Vec::with_capacity(items.len() * 2);
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            body.push(Doc::Line);
+        }
+        body.push(item);
+    }
+
+    Doc::Align(Box::new(Doc::Group(Box::new(Doc::Concat(vec![
+        Doc::Text(open.into()),
+        Doc::Nest(nest, Box::new(Doc::Concat(body))),
+        Doc::Text(close.into()),
+    ])))))
+}
+
+fn is_atom(value: &MalType) -> bool {
+    !matches!(
+        value,
+        MalType::List { .. }
+            | MalType::Vector { .. }
+            | MalType::HashMap { .. }
+            | MalType::Set { .. }
+    )
+}
+
+fn render(doc: &Doc, width: usize) -> String {
+    let mut out = String::new();
+    let mut col = 0;
+    let mut stack: Vec<Frame> = vec![(0, Mode::Break, doc)];
+
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                out.push_str(text);
+                col += text.chars().count();
+            }
+            Doc::Line if mode == Mode::Flat => {
+                out.push(' ');
+                col += 1;
+            }
+            Doc::Line => {
+                out.push('\n');
+                out.extend(std::iter::repeat_n(' ', indent));
+                col = indent;
+            }
+            Doc::Nest(nest, doc) => stack.push((indent + nest, mode, doc)),
+            Doc::Align(doc) => stack.push((col, mode, doc)),
+            Doc::Fill(atoms) => {
+                for (i, atom) in atoms.iter().enumerate() {
+                    let len = atom.chars().count();
+                    if i > 0 && mode == Mode::Break && col + 1 + len > width {
+                        out.push('\n');
+                        out.extend(std::iter::repeat_n(' ', indent));
+                        col = indent;
+                    } else if i > 0 {
+                        out.push(' ');
+                        col += 1;
+                    }
+                    out.push_str(atom);
+                    col += len;
+                }
+            }
+            Doc::Group(doc) => {
+                let flat = (indent, Mode::Flat, doc.as_ref());
+                let mode = if fits(width as isize - col as isize, flat, &stack) {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+                stack.push((indent, mode, doc));
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+        }
+    }
+
+    out
+}
+
+// Whether the group fits in the remaining width, counting whatever follows it up to the next
+// line break that is already known to be rendered as a newline.
+fn fits(mut remaining: isize, group: Frame, rest: &[Frame]) -> bool {
+    let mut pending = vec![group];
+    let mut rest = rest.iter().rev();
+
+    while remaining >= 0 {
+        let Some((indent, mode, doc)) = pending.pop().or_else(|| rest.next().copied()) else {
+            return true;
+        };
+        match doc {
+            Doc::Text(text) => remaining -= text.chars().count() as isize,
+            Doc::Line if mode == Mode::Flat => remaining -= 1,
+            Doc::Line => return true,
+            Doc::Nest(nest, doc) => pending.push((indent + nest, mode, doc)),
+            Doc::Align(doc) | Doc::Group(doc) => pending.push((indent, mode, doc)),
+            Doc::Fill(atoms) if mode == Mode::Flat => {
+                remaining -= atoms.join(" ").chars().count() as isize
+            }
+            Doc::Fill(atoms) => {
+                let first = atoms.first().map_or(0, |atom| atom.chars().count());
+                return remaining >= first as isize;
+            }
+            Doc::Concat(docs) => pending.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pretty_str(src: &str, width: usize, indent: usize) -> String {
+        pretty(
+            &reader::read_string(src).unwrap(),
+            &Options { width, indent },
+        )
+    }
+
+    #[test]
+    fn breaks_only_groups_that_do_not_fit() {
+        assert_eq!(pretty_str("(a (b c) (d e))", 80, 1), "(a (b c) (d e))");
+        assert_eq!(pretty_str("(a (b c) (d e))", 8, 1), "(a\n (b c)\n (d e))");
+        assert_eq!(
+            pretty_str("(a (b c d e f g) (h))", 14, 1),
+            "(a\n (b c d e f g)\n (h))"
+        );
+        assert_eq!(
+            pretty_str("[1 2 3 4 5 6 7 8 9 10]", 10, 1),
+            "[1 2 3 4 5\n 6 7 8 9\n 10]"
+        );
+    }
+
+    #[test]
+    fn indents_broken_lines_by_the_given_amount() {
+        assert_eq!(pretty_str("(a (b c) (d e))", 8, 2), "(a\n  (b c)\n  (d e))");
+        assert_eq!(pretty_str("(a (b c) (d e))", 8, 0), "(a\n(b c)\n(d e))");
+        assert_eq!(
+            pretty_str("(x (a (b c) (d e)))", 16, 4),
+            "(x\n    (a\n        (b c)\n        (d e)))"
+        );
+    }
+}
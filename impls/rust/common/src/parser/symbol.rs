@@ -0,0 +1,87 @@
+use std::collections::HashSet;
+use std::fmt::{self, Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::ptr;
+use std::sync::{LazyLock, RwLock};
+
+// Symbols point into a process-wide table holding one copy of each name, so comparing and
+// hashing them only looks at the pointer and reading the name takes no lock. Interned names are
+// never freed; that includes keywords created from data by json-parse, read-edn, csv-read and
+// deserialize, so reading untrusted data with many distinct keys grows the table for good.
+#[derive(Clone, Copy)]
+pub struct Symbol(&'static str);
+
+static INTERNER: LazyLock<RwLock<HashSet<&'static str>>> = LazyLock::new(Default::default);
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        if let Some(&name) = INTERNER.read().unwrap().get(name) {
+            return Symbol(name);
+        }
+
+        let mut interner = INTERNER.write().unwrap();
+        if let Some(&name) = interner.get(name) {
+            return Symbol(name);
+        }
+        let name: &'static str = Box::leak(name.into());
+        interner.insert(name);
+        Symbol(name)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ptr::hash(self.0, state);
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol::intern(&name)
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.0, f)
+    }
+}
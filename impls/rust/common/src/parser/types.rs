@@ -1,25 +1,64 @@
+use std::cell::RefCell;
 use std::fmt::Display;
+use std::hash::{BuildHasher, BuildHasherDefault, DefaultHasher, Hash, Hasher};
+use std::mem;
+use std::ptr;
+use std::rc::Rc;
+
+use im_rc::{HashMap, HashSet, Vector};
+use regex::Regex;
 
 use crate::parser::errors::ReplError;
+use crate::parser::symbol::Symbol;
+
+pub type Builtin = fn(MalType) -> Result<MalType, ReplError>;
+
+// Collections are persistent RRB vectors, so cloning one is O(1) and updating it copies only
+// the path to the changed element.
+pub type Tokens = Vector<MalType>;
+
+// Hash-maps and sets are persistent hash tries, so lookups and inserts are effectively O(1).
+// Their hasher has fixed keys, so they iterate and print in the same order on every run.
+pub type FixedState = BuildHasherDefault<DefaultHasher>;
+pub type Entries = HashMap<MalType, MalType, FixedState>;
+pub type Members = HashSet<MalType, FixedState>;
 
 #[derive(Clone, Debug, Default)]
 pub enum MalType {
-    String(Box<str>),
+    String(Rc<str>),
     Number(i128),
-    Symbol(Box<str>),
+    Float(f64),
+    Symbol(Symbol),
     Bool(bool),
     #[default]
     Nil,
     List {
-        tokens: Vec<MalType>,
+        tokens: Tokens,
     },
     Vector {
-        tokens: Vec<MalType>,
+        tokens: Tokens,
     },
     HashMap {
-        tokens: Vec<MalType>,
+        entries: Entries,
     },
-    Function(fn(MalType) -> Result<MalType, ReplError>),
+    Set {
+        members: Members,
+    },
+    Regex(Regex),
+    Function {
+        name: &'static str,
+        func: Builtin,
+    },
+    Atom(Rc<RefCell<MalType>>),
+}
+
+// Consecutive pairs of tokens, as in let* bindings and hash-map literals. A trailing token without
+// a partner is dropped.
+pub fn pairs(tokens: &Tokens) -> impl Iterator<Item = (&MalType, &MalType)> {
+    tokens
+        .iter()
+        .step_by(2)
+        .zip(tokens.iter().skip(1).step_by(2))
 }
 
 #[derive(Clone, Debug)]
@@ -27,20 +66,141 @@ pub enum MalCollection {
     List,
     Vector,
     HashMap,
+    Set,
+}
+
+// Floats are equal when they are identical, down to the sign of a zero and the payload of a NaN,
+// so that equal values always hash the same.
+impl PartialEq for MalType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Symbol(a), Self::Symbol(b)) => a == b,
+            (Self::Number(a), Self::Number(b)) => a == b,
+            (Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Nil, Self::Nil) => true,
+            (Self::List { tokens: a }, Self::List { tokens: b })
+            | (Self::Vector { tokens: a }, Self::Vector { tokens: b }) => a == b,
+            (Self::HashMap { entries: a }, Self::HashMap { entries: b }) => a == b,
+            (Self::Set { members: a }, Self::Set { members: b }) => a == b,
+            (Self::Regex(a), Self::Regex(b)) => a.as_str() == b.as_str(),
+            (Self::Function { func: a, .. }, Self::Function { func: b, .. }) => {
+                ptr::fn_addr_eq(*a, *b)
+            }
+            (Self::Atom(a), Self::Atom(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for MalType {}
+
+// Symbols are hashed by name rather than by their interned address, which changes between runs.
+impl Hash for MalType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Self::String(string) => string.hash(state),
+            Self::Number(number) => number.hash(state),
+            Self::Float(number) => number.to_bits().hash(state),
+            Self::Symbol(symbol) => symbol.as_str().hash(state),
+            Self::Bool(boolean) => boolean.hash(state),
+            Self::Nil => {}
+            Self::List { tokens } | Self::Vector { tokens } => tokens.hash(state),
+            Self::HashMap { entries } => hash_unordered(entries.iter(), state),
+            Self::Set { members } => hash_unordered(members.iter(), state),
+            Self::Regex(regex) => regex.as_str().hash(state),
+            Self::Function { func, .. } => (*func as usize).hash(state),
+            Self::Atom(atom) => Rc::as_ptr(atom).hash(state),
+        }
+    }
+}
+
+// Equal hash-maps and sets may iterate in different orders, so their elements are hashed on their
+// own and summed.
+fn hash_unordered<T: Hash, H: Hasher>(elements: impl ExactSizeIterator<Item = T>, state: &mut H) {
+    elements.len().hash(state);
+    elements
+        .map(|element| FixedState::default().hash_one(element))
+        .fold(0u64, u64::wrapping_add)
+        .hash(state);
+}
+
+impl MalType {
+    pub fn string(value: &str) -> MalType {
+        MalType::String(value.into())
+    }
+
+    fn keyword(name: &str) -> MalType {
+        MalType::Symbol(format!(":{name}").into())
+    }
+}
+
+impl From<&ReplError> for MalType {
+    fn from(error: &ReplError) -> Self {
+        let mut entries = Entries::default();
+        entries.insert(MalType::keyword("type"), MalType::keyword(error.kind()));
+        entries.insert(
+            MalType::keyword("message"),
+            MalType::string(&error.to_string()),
+        );
+
+        match error {
+            ReplError::Unclosed(delimiter) => {
+                entries.insert(
+                    MalType::keyword("delimiter"),
+                    MalType::string(&delimiter.to_string()),
+                );
+            }
+            ReplError::Escape(sequence) => {
+                entries.insert(MalType::keyword("sequence"), MalType::string(sequence));
+            }
+            ReplError::Arguments(operation) => {
+                entries.insert(
+                    MalType::keyword("operation"),
+                    MalType::Symbol(operation.as_ref().into()),
+                );
+            }
+            ReplError::Type { expected, received } => {
+                entries.insert(MalType::keyword("expected"), MalType::string(expected));
+                entries.insert(MalType::keyword("received"), MalType::string(received));
+            }
+            ReplError::Csv { line, .. } => {
+                entries.insert(MalType::keyword("line"), MalType::Number(*line as i128));
+            }
+            ReplError::Json { line, column, .. } => {
+                entries.insert(MalType::keyword("line"), MalType::Number(*line as i128));
+                entries.insert(MalType::keyword("column"), MalType::Number(*column as i128));
+            }
+            ReplError::UnknownSymbol(symbol) => {
+                entries.insert(
+                    MalType::keyword("symbol"),
+                    MalType::Symbol(symbol.as_ref().into()),
+                );
+            }
+            _ => {}
+        }
+
+        MalType::HashMap { entries }
+    }
 }
 
 impl Display for MalType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let variant = match &self {
             Self::String(_) => "string",
-            Self::Number(_) => "number",
+            Self::Number(_) | Self::Float(_) => "number",
             Self::Symbol(_) => "symbol",
             Self::Bool(_) => "bool",
             Self::Nil => "nil",
             Self::List { tokens: _ } => "list",
             Self::Vector { tokens: _ } => "vector",
-            Self::HashMap { tokens: _ } => "hashmap",
-            Self::Function(_) => "function",
+            Self::HashMap { .. } => "hashmap",
+            Self::Set { .. } => "set",
+            Self::Regex(_) => "regex",
+            Self::Function { .. } => "function",
+            Self::Atom(_) => "atom",
         };
 
         write!(f, "{variant}")
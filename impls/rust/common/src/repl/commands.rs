@@ -0,0 +1,82 @@
+use std::io::{self, Write};
+use std::time::Instant;
+
+use crate::parser::{self, errors::ReplError};
+use crate::repl::highlight;
+
+pub enum Command {
+    Continue,
+    Quit,
+}
+
+pub const COMMANDS: &[&str] = &[
+    ":help", ":env", ":load", ":time", ":reset", ":debug", ":quit",
+];
+
+const HELP: &str = "\
+:help           Show this message.
+:env [PREFIX]   List the bindings in the current environment.
+:load FILE      Evaluate every form in FILE.
+:time EXPR      Evaluate EXPR and report how long it took.
+:reset          Discard every definition and start from a fresh environment.
+:debug          Toggle DEBUG-EVAL.
+:quit           Leave the REPL.
+";
+
+pub fn command(line: &str) -> Option<Command> {
+    let line = line.trim();
+    let (name, arg) = match line.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (line, ""),
+    };
+
+    let output = match name {
+        ":help" | ":h" => Ok(HELP.to_string()),
+        ":env" => Ok(env(arg)),
+        ":load" | ":l" if !arg.is_empty() => {
+            parser::load_file(arg).map(|_| format!("Loaded {arg}.\n"))
+        }
+        ":time" | ":t" if !arg.is_empty() => time(arg),
+        ":reset" => parser::reset().map(|_| "Environment reset.\n".to_string()),
+        ":debug" => {
+            let state = if parser::toggle_debug() { "on" } else { "off" };
+            Ok(format!("DEBUG-EVAL is {state}.\n"))
+        }
+        ":quit" | ":q" => return Some(Command::Quit),
+        _ => return None,
+    };
+
+    let output = match output {
+        Ok(output) => output,
+        Err(ReplError::Unclosed(_)) => "unbalanced\n".to_string(),
+        Err(e) => format!("{e}\n"),
+    };
+
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(output.as_bytes());
+    let _ = stdout.flush();
+
+    Some(Command::Continue)
+}
+
+fn env(prefix: &str) -> String {
+    let mut out = String::new();
+    for (key, value) in parser::bindings() {
+        if key.starts_with(prefix) {
+            out.push_str(&format!("{key} = {}", highlight::colorize(&value)));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn time(expr: &str) -> Result<String, ReplError> {
+    let start = Instant::now();
+    let res = parser::rep_with_history(expr)?;
+    let elapsed = start.elapsed();
+    Ok(format!(
+        "{}Elapsed time: {:.3} ms\n",
+        highlight::colorize(&res),
+        elapsed.as_secs_f64() * 1000.0
+    ))
+}
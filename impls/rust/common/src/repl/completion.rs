@@ -0,0 +1,70 @@
+use crate::parser;
+use crate::repl::commands::COMMANDS;
+
+pub struct Completion {
+    pub start: usize,
+    pub candidates: Vec<Box<str>>,
+}
+
+pub fn complete(buffer: &[char], cursor: usize) -> Completion {
+    let mut start = cursor;
+    while start > 0 && !is_delimiter(buffer[start - 1]) {
+        start -= 1;
+    }
+
+    let prefix: String = buffer[start..cursor].iter().collect();
+    let symbols = if start == 0 && prefix.starts_with(':') {
+        COMMANDS.iter().map(|&command| command.into()).collect()
+    } else {
+        parser::symbols()
+    };
+    let candidates = symbols
+        .into_iter()
+        .filter(|symbol| symbol.starts_with(&prefix))
+        .collect();
+
+    Completion { start, candidates }
+}
+
+pub fn common_prefix(candidates: &[Box<str>]) -> &str {
+    let Some(first) = candidates.first() else {
+        return "";
+    };
+
+    let mut len = first.len();
+    for candidate in &candidates[1..] {
+        len = first
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((index, char), _)| index + char.len_utf8())
+            .min(len);
+    }
+
+    &first[..len]
+}
+
+pub fn columns(candidates: &[Box<str>], width: usize) -> String {
+    let column_width = candidates
+        .iter()
+        .map(|candidate| candidate.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 2;
+    let per_row = (width / column_width).max(1);
+
+    let mut out = String::new();
+    for row in candidates.chunks(per_row) {
+        for candidate in row {
+            out.push_str(&format!("{candidate:column_width$}"));
+        }
+        out.truncate(out.trim_end().len());
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn is_delimiter(char: char) -> bool {
+    char.is_whitespace() || "()[]{}'\"`,;~@^".contains(char)
+}
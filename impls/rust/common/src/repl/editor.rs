@@ -0,0 +1,378 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crate::repl::completion;
+use crate::repl::highlight;
+use crate::repl::history::History;
+use crate::repl::terminal::{self, Key, RawMode};
+
+const FLASH_MILLIS: i32 = 150;
+
+pub struct Editor {
+    history: History,
+    interactive: bool,
+}
+
+struct Line<'a> {
+    prompt: &'a str,
+    buffer: Vec<char>,
+    cursor: usize,
+    history_index: usize,
+    pending: Vec<char>,
+}
+
+struct Search {
+    query: String,
+    found: Option<usize>,
+    original: Vec<char>,
+    cursor: usize,
+}
+
+enum Action {
+    Continue,
+    Submit,
+    Eof,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        let interactive =
+            io::stdin().is_terminal() && io::stdout().is_terminal() && terminal::capable();
+
+        let history = if interactive {
+            History::load()
+        } else {
+            History::default()
+        };
+
+        Editor {
+            history,
+            interactive,
+        }
+    }
+
+    pub fn readline(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        if !self.interactive {
+            return read_plain(prompt);
+        }
+
+        let line = {
+            let _raw = RawMode::enable()?;
+            self.edit(prompt)?
+        };
+
+        if let Some(line) = &line {
+            // A history file we cannot write to should not take the prompt down with it.
+            let _ = self.history.add(line);
+        }
+
+        Ok(line)
+    }
+
+    fn edit(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        let mut line = Line {
+            prompt,
+            buffer: Vec::new(),
+            cursor: 0,
+            history_index: self.history.len(),
+            pending: Vec::new(),
+        };
+        let mut search: Option<Search> = None;
+
+        refresh(&line)?;
+
+        loop {
+            let key = match terminal::read_key()? {
+                Some(key) => key,
+                None => return Ok(None),
+            };
+
+            let key = match &mut search {
+                Some(state) => match self.search_key(&mut line, state, key) {
+                    Some(key) => {
+                        search = None;
+                        refresh(&line)?;
+                        key
+                    }
+                    None => {
+                        refresh_search(&line, state, &self.history)?;
+                        continue;
+                    }
+                },
+                None => key,
+            };
+
+            match key {
+                Key::Escape | Key::Ctrl('g') => continue,
+                Key::Ctrl('r') => {
+                    let state = Search {
+                        query: String::new(),
+                        found: None,
+                        original: line.buffer.clone(),
+                        cursor: line.cursor,
+                    };
+                    refresh_search(&line, &state, &self.history)?;
+                    search = Some(state);
+                }
+                key => match self.key(&mut line, key)? {
+                    Action::Continue => {}
+                    Action::Submit => return Ok(Some(line.buffer.iter().collect())),
+                    Action::Eof => return Ok(None),
+                },
+            }
+        }
+    }
+
+    fn key(&mut self, line: &mut Line, key: Key) -> io::Result<Action> {
+        match key {
+            Key::Enter => {
+                line.cursor = line.buffer.len();
+                refresh(line)?;
+                write_out("\r\n")?;
+                return Ok(Action::Submit);
+            }
+            Key::Ctrl('d') => {
+                if line.buffer.is_empty() {
+                    write_out("\r\n")?;
+                    return Ok(Action::Eof);
+                }
+                if line.cursor < line.buffer.len() {
+                    line.buffer.remove(line.cursor);
+                }
+            }
+            Key::Ctrl('c') => {
+                write_out("^C\r\n")?;
+                line.buffer.clear();
+                line.cursor = 0;
+                line.history_index = self.history.len();
+            }
+            Key::Char(char) => {
+                line.buffer.insert(line.cursor, char);
+                line.cursor += 1;
+            }
+            Key::Backspace if line.cursor > 0 => {
+                line.cursor -= 1;
+                line.buffer.remove(line.cursor);
+            }
+            Key::Delete if line.cursor < line.buffer.len() => {
+                line.buffer.remove(line.cursor);
+            }
+            Key::Left | Key::Ctrl('b') => line.cursor = line.cursor.saturating_sub(1),
+            Key::Right | Key::Ctrl('f') => line.cursor = (line.cursor + 1).min(line.buffer.len()),
+            Key::Home | Key::Ctrl('a') => line.cursor = 0,
+            Key::End | Key::Ctrl('e') => line.cursor = line.buffer.len(),
+            Key::Ctrl('k') => line.buffer.truncate(line.cursor),
+            Key::Ctrl('u') => {
+                line.buffer.drain(..line.cursor);
+                line.cursor = 0;
+            }
+            Key::Ctrl('w') => {
+                let mut start = line.cursor;
+                while start > 0 && line.buffer[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                while start > 0 && !line.buffer[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                line.buffer.drain(start..line.cursor);
+                line.cursor = start;
+            }
+            Key::Tab => complete(line)?,
+            Key::Ctrl('l') => write_out("\x1b[H\x1b[2J")?,
+            Key::Up | Key::Ctrl('p') => self.history_move(line, -1),
+            Key::Down | Key::Ctrl('n') => self.history_move(line, 1),
+            _ => {}
+        }
+
+        refresh(line)?;
+
+        if let Key::Char(')' | ']' | '}') = key
+            && let Some(open) = highlight::matching_open(&line.buffer, line.cursor - 1)
+        {
+            render(line, Some(open))?;
+            terminal::wait_for_input(FLASH_MILLIS);
+            refresh(line)?;
+        }
+
+        Ok(Action::Continue)
+    }
+
+    fn history_move(&self, line: &mut Line, direction: isize) {
+        let index = line.history_index as isize + direction;
+        if index < 0 || index as usize > self.history.len() {
+            return;
+        }
+
+        if line.history_index == self.history.len() {
+            line.pending = line.buffer.clone();
+        }
+        line.history_index = index as usize;
+
+        line.buffer = match self.history.get(line.history_index) {
+            Some(entry) => entry.chars().collect(),
+            None => line.pending.clone(),
+        };
+        line.cursor = line.buffer.len();
+    }
+
+    // Returns the key that ended the search, or None while the search is still going.
+    fn search_key(&self, line: &mut Line, state: &mut Search, key: Key) -> Option<Key> {
+        match key {
+            Key::Char(char) => {
+                state.query.push(char);
+                let start = state.found.map_or(self.history.len(), |found| found + 1);
+                state.found = self.history.search(&state.query, start);
+            }
+            Key::Backspace => {
+                state.query.pop();
+                state.found = self.history.search(&state.query, self.history.len());
+            }
+            Key::Ctrl('r') => {
+                let start = state.found.unwrap_or(self.history.len());
+                if let Some(found) = self.history.search(&state.query, start) {
+                    state.found = Some(found);
+                }
+            }
+            Key::Escape | Key::Ctrl('g') => {
+                line.buffer = state.original.clone();
+                line.cursor = state.cursor;
+                return Some(key);
+            }
+            other => {
+                if let Some(entry) = state.found.and_then(|found| self.history.get(found)) {
+                    line.buffer = entry.chars().collect();
+                    line.cursor = line.buffer.len();
+                    line.history_index = self.history.len();
+                }
+                return Some(other);
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn read_plain(prompt: &str) -> io::Result<Option<String>> {
+    write_out(prompt)?;
+
+    let mut buffer = String::new();
+    if io::stdin().lock().read_line(&mut buffer)? == 0 {
+        return Ok(None);
+    }
+
+    if buffer.ends_with('\n') {
+        buffer.pop();
+        if buffer.ends_with('\r') {
+            buffer.pop();
+        }
+    }
+
+    Ok(Some(buffer))
+}
+
+fn complete(line: &mut Line) -> io::Result<()> {
+    let completion = completion::complete(&line.buffer, line.cursor);
+    let typed = line.cursor - completion.start;
+
+    let insert: Vec<char> = match completion.candidates.as_slice() {
+        [] => return write_out("\x07"),
+        [candidate] => {
+            let mut insert: Vec<char> = candidate.chars().skip(typed).collect();
+            if line
+                .buffer
+                .get(line.cursor)
+                .is_none_or(|next| !next.is_whitespace())
+            {
+                insert.push(' ');
+            }
+            insert
+        }
+        candidates => {
+            let common = completion::common_prefix(candidates);
+            if common.chars().count() <= typed {
+                let listing = completion::columns(candidates, terminal::width());
+                return write_out(&format!("\r\n{listing}"));
+            }
+            common.chars().skip(typed).collect()
+        }
+    };
+
+    let len = insert.len();
+    line.buffer.splice(line.cursor..line.cursor, insert);
+    line.cursor += len;
+    Ok(())
+}
+
+fn write_out(str: &str) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(str.as_bytes())?;
+    stdout.flush()
+}
+
+fn refresh(line: &Line) -> io::Result<()> {
+    render(line, None)
+}
+
+fn render(line: &Line, flash: Option<usize>) -> io::Result<()> {
+    let prompt_len = line.prompt.chars().count();
+    let available = terminal::width().saturating_sub(prompt_len + 1).max(1);
+    let start = line.cursor.saturating_sub(available);
+    let end = line.buffer.len().min(start + available);
+
+    let mut out = String::from("\r");
+    out.push_str(line.prompt);
+    if highlight::enabled() {
+        let styles = highlight::styles(&line.buffer.iter().collect::<String>());
+        let visible = line.buffer[start..end].iter().zip(&styles[start..end]);
+        let mut current = None;
+        for (index, (&char, &style)) in (start..).zip(visible) {
+            let style = if flash == Some(index) {
+                Some(highlight::MATCH)
+            } else {
+                style
+            };
+            if style != current {
+                if current.is_some() {
+                    out.push_str(highlight::RESET);
+                }
+                if let Some(style) = style {
+                    out.push_str(style);
+                }
+                current = style;
+            }
+            out.push(char);
+        }
+        if current.is_some() {
+            out.push_str(highlight::RESET);
+        }
+    } else {
+        out.extend(&line.buffer[start..end]);
+    }
+    out.push_str("\x1b[K\r");
+    let column = prompt_len + line.cursor - start;
+    if column > 0 {
+        out.push_str(&format!("\x1b[{column}C"));
+    }
+
+    write_out(&out)
+}
+
+fn refresh_search(line: &Line, state: &Search, history: &History) -> io::Result<()> {
+    let label = if state.found.is_none() && !state.query.is_empty() {
+        "failing reverse-i-search"
+    } else {
+        "reverse-i-search"
+    };
+    let entry = match state.found.and_then(|found| history.get(found)) {
+        Some(entry) => entry.to_owned(),
+        None => line.buffer.iter().collect(),
+    };
+
+    let out = format!("\r({label})`{}': {}\x1b[K", state.query, entry);
+    write_out(&out)
+}
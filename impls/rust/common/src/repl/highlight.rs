@@ -0,0 +1,94 @@
+use std::env;
+use std::io::{self, IsTerminal};
+use std::sync::LazyLock;
+
+use crate::parser::{self, TokenKind};
+use crate::repl::terminal;
+
+pub const RESET: &str = "\x1b[0m";
+pub const MATCH: &str = "\x1b[7m";
+
+static ENABLED: LazyLock<bool> = LazyLock::new(|| {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none() && terminal::capable()
+});
+
+pub fn enabled() -> bool {
+    *ENABLED
+}
+
+fn style(kind: TokenKind) -> Option<&'static str> {
+    match kind {
+        TokenKind::String => Some("\x1b[32m"),
+        TokenKind::Number => Some("\x1b[36m"),
+        TokenKind::Keyword => Some("\x1b[35m"),
+        TokenKind::Constant => Some("\x1b[33m"),
+        TokenKind::SpecialForm => Some("\x1b[1;34m"),
+        TokenKind::Comment => Some("\x1b[90m"),
+        TokenKind::Macro => Some("\x1b[33m"),
+        TokenKind::Open | TokenKind::Close | TokenKind::Symbol => None,
+    }
+}
+
+// One style per char of `line`, so the editor can colour any window of it.
+pub fn styles(line: &str) -> Vec<Option<&'static str>> {
+    let mut styles = vec![None; line.chars().count()];
+    let indices = char_indices(line);
+    for (range, kind) in parser::classify(line) {
+        let style = style(kind);
+        for styled in &mut styles[indices[range.start]..indices[range.end]] {
+            *styled = style;
+        }
+    }
+    styles
+}
+
+pub fn colorize(text: &str) -> String {
+    if !enabled() {
+        return text.to_owned();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (range, kind) in parser::classify(text) {
+        let Some(style) = style(kind) else {
+            continue;
+        };
+        out.push_str(&text[last..range.start]);
+        out.push_str(style);
+        out.push_str(&text[range.clone()]);
+        out.push_str(RESET);
+        last = range.end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+pub fn matching_open(buffer: &[char], close: usize) -> Option<usize> {
+    let line: String = buffer.iter().collect();
+    let indices = char_indices(&line);
+    let mut stack = Vec::new();
+
+    for (range, kind) in parser::classify(&line) {
+        let index = indices[range.start];
+        match kind {
+            TokenKind::Open => stack.push(index),
+            TokenKind::Close if index == close => return stack.pop(),
+            TokenKind::Close => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// Maps every byte offset of `line` (plus its end) to the index of the char it falls in.
+fn char_indices(line: &str) -> Vec<usize> {
+    let mut indices = Vec::with_capacity(line.len() + 1);
+    for (index, char) in line.chars().enumerate() {
+        indices.extend(std::iter::repeat_n(index, char.len_utf8()));
+    }
+    indices.push(line.chars().count());
+    indices
+}
@@ -0,0 +1,104 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+const MAX_ENTRIES: usize = 1000;
+const FILE_NAME: &str = ".mal_history";
+
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    pub fn load() -> Self {
+        let path = env::var_os("HOME").map(|home| PathBuf::from(home).join(FILE_NAME));
+        let mut entries: Vec<String> = match &path {
+            Some(path) => fs::read_to_string(path)
+                .map(|contents| contents.lines().map(unescape).collect())
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+
+        if entries.len() > MAX_ENTRIES {
+            entries.drain(..entries.len() - MAX_ENTRIES);
+        }
+
+        History { entries, path }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    pub fn add(&mut self, line: &str) -> io::Result<()> {
+        if line.trim().is_empty() || self.entries.last().is_some_and(|last| last == line) {
+            return Ok(());
+        }
+
+        self.entries.push(line.to_owned());
+
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+            return self.save();
+        }
+
+        match &self.path {
+            Some(path) => {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                writeln!(file, "{}", escape(line))
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        if query.is_empty() {
+            return None;
+        }
+
+        self.entries[..before.min(self.entries.len())]
+            .iter()
+            .rposition(|entry| entry.contains(query))
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut contents = String::new();
+        for entry in &self.entries {
+            contents.push_str(&escape(entry));
+            contents.push('\n');
+        }
+        fs::write(path, contents)
+    }
+}
+
+fn escape(line: &str) -> String {
+    line.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut ret = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            ret.push(char);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => ret.push('\n'),
+            Some(other) => ret.push(other),
+            None => ret.push('\\'),
+        }
+    }
+    ret
+}
@@ -0,0 +1,31 @@
+use std::cell::RefCell;
+use std::io;
+
+use crate::repl::editor::Editor;
+
+pub use crate::repl::commands::{Command, command};
+
+mod commands;
+mod completion;
+mod editor;
+mod highlight;
+mod history;
+mod terminal;
+
+thread_local! {
+    static EDITOR: RefCell<Editor> = RefCell::new(Editor::new());
+}
+
+pub fn colorize(text: &str) -> String {
+    highlight::colorize(text)
+}
+
+// The width results should be pretty printed to. Results are only wrapped under the same
+// conditions as highlighting, so tests, pipes and dumb terminals get one line per result.
+pub fn width() -> Option<usize> {
+    highlight::enabled().then(terminal::width)
+}
+
+pub fn readline(prompt: &str) -> io::Result<Option<String>> {
+    EDITOR.with(|editor| editor.borrow_mut().readline(prompt))
+}
@@ -0,0 +1,185 @@
+use std::env;
+use std::io;
+use std::mem::MaybeUninit;
+
+// An unset TERM is treated like a dumb terminal.
+pub fn capable() -> bool {
+    env::var("TERM").is_ok_and(|term| term != "dumb")
+}
+
+pub fn width() -> usize {
+    let mut size = MaybeUninit::<libc::winsize>::zeroed();
+    let res = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, size.as_mut_ptr()) };
+    let size = unsafe { size.assume_init() };
+    if res == -1 || size.ws_col == 0 {
+        80
+    } else {
+        size.ws_col as usize
+    }
+}
+
+pub struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    pub fn enable() -> io::Result<RawMode> {
+        let mut termios = MaybeUninit::<libc::termios>::zeroed();
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let original = unsafe { termios.assume_init() };
+
+        let mut raw = original;
+        raw.c_iflag &= !(libc::BRKINT | libc::ICRNL | libc::INPCK | libc::ISTRIP | libc::IXON);
+        raw.c_oflag &= !libc::OPOST;
+        raw.c_cflag |= libc::CS8;
+        raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::IEXTEN | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(RawMode { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Ctrl(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Escape,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Unknown,
+}
+
+pub fn read_key() -> io::Result<Option<Key>> {
+    let byte = match read_byte()? {
+        Some(byte) => byte,
+        None => return Ok(None),
+    };
+
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x1b => read_escape()?,
+        0x01..=0x1a => Key::Ctrl((byte - 1 + b'a') as char),
+        0x00..=0x1f => Key::Unknown,
+        0x20..=0x7e => Key::Char(byte as char),
+        _ => read_utf8(byte)?,
+    };
+
+    Ok(Some(key))
+}
+
+fn read_byte() -> io::Result<Option<u8>> {
+    let mut buf = [0u8; 1];
+    loop {
+        let read = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), 1) };
+        match read {
+            0 => return Ok(None),
+            1 => return Ok(Some(buf[0])),
+            _ => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+pub fn wait_for_input(timeout: i32) -> bool {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut fd, 1, timeout) > 0 }
+}
+
+fn read_escape() -> io::Result<Key> {
+    if !wait_for_input(50) {
+        return Ok(Key::Escape);
+    }
+
+    let key = match read_byte()? {
+        Some(b'[') => match read_byte()? {
+            Some(b'A') => Key::Up,
+            Some(b'B') => Key::Down,
+            Some(b'C') => Key::Right,
+            Some(b'D') => Key::Left,
+            Some(b'H') => Key::Home,
+            Some(b'F') => Key::End,
+            Some(digit @ b'0'..=b'9') => {
+                let mut code = (digit - b'0') as u32;
+                loop {
+                    match read_byte()? {
+                        Some(next @ b'0'..=b'9') => code = code * 10 + (next - b'0') as u32,
+                        Some(b'~') => break,
+                        _ => return Ok(Key::Unknown),
+                    }
+                }
+                match code {
+                    1 | 7 => Key::Home,
+                    3 => Key::Delete,
+                    4 | 8 => Key::End,
+                    _ => Key::Unknown,
+                }
+            }
+            _ => Key::Unknown,
+        },
+        Some(b'O') => match read_byte()? {
+            Some(b'H') => Key::Home,
+            Some(b'F') => Key::End,
+            _ => Key::Unknown,
+        },
+        _ => Key::Escape,
+    };
+
+    Ok(key)
+}
+
+fn read_utf8(first: u8) -> io::Result<Key> {
+    let len = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Ok(Key::Unknown),
+    };
+
+    let mut bytes = vec![first];
+    for _ in 1..len {
+        match read_byte()? {
+            Some(byte) => bytes.push(byte),
+            None => return Ok(Key::Unknown),
+        }
+    }
+
+    let key = match std::str::from_utf8(&bytes) {
+        Ok(str) => str.chars().next().map_or(Key::Unknown, Key::Char),
+        Err(_) => Key::Unknown,
+    };
+
+    Ok(key)
+}
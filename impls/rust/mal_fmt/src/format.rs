@@ -0,0 +1,263 @@
+use common::parser::cst::{self, Node};
+use common::parser::errors::ReplError;
+
+// Forms whose arguments after the first line are indented as a body rather than aligned.
+const BODY_FORMS: &[&str] = &[
+    "def!",
+    "defmacro!",
+    "let*",
+    "fn*",
+    "do",
+    "if",
+    "try*",
+    "catch*",
+    "let",
+    "fn",
+    "defn",
+    "defmacro",
+    "when",
+    "when-not",
+    "cond",
+    "loop",
+    "binding",
+    "case",
+];
+
+const BINDING_FORMS: &[&str] = &["let*", "let", "loop", "binding"];
+
+const MAX_BREAKS: usize = 2;
+
+struct Item<'a> {
+    node: &'a Node,
+    breaks: usize,
+    comma: bool,
+}
+
+impl Item<'_> {
+    fn is_comment(&self) -> bool {
+        matches!(self.node, Node::Comment(_))
+    }
+}
+
+#[derive(Default)]
+struct Formatter {
+    out: String,
+    col: usize,
+}
+
+pub fn format(src: &str) -> Result<String, ReplError> {
+    let nodes = cst::parse(src)?;
+    let items = items(&nodes);
+
+    let mut formatter = Formatter::default();
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            if item.breaks > 0 || items[i - 1].is_comment() {
+                formatter.newline(item.breaks.clamp(1, MAX_BREAKS), 0);
+            } else {
+                formatter.write(" ");
+            }
+        }
+        formatter.node(item.node, false);
+        if item.comma {
+            formatter.write(",");
+        }
+    }
+
+    if !formatter.out.is_empty() {
+        formatter.out.push('\n');
+    }
+    Ok(formatter.out)
+}
+
+// Folds whitespace and commas into the forms and comments around them, keeping only the number
+// of line breaks in front of each one.
+fn items(nodes: &[Node]) -> Vec<Item<'_>> {
+    let mut items: Vec<Item> = Vec::new();
+    let mut breaks = 0;
+
+    for node in nodes {
+        match node {
+            Node::Whitespace(text) => breaks += text.matches('\n').count(),
+            Node::Comma(_) => match items.last_mut() {
+                Some(last) if breaks == 0 && !last.is_comment() => last.comma = true,
+                _ => {}
+            },
+            node => {
+                items.push(Item {
+                    node,
+                    breaks,
+                    comma: false,
+                });
+                breaks = 0;
+            }
+        }
+    }
+
+    items
+}
+
+impl Formatter {
+    fn write(&mut self, text: &str) {
+        self.out.push_str(text);
+        match text.rfind('\n') {
+            Some(index) => self.col = text[index + 1..].chars().count(),
+            None => self.col += text.chars().count(),
+        }
+    }
+
+    fn newline(&mut self, breaks: usize, indent: usize) {
+        self.out.truncate(self.out.trim_end_matches(' ').len());
+        for _ in 0..breaks {
+            self.out.push('\n');
+        }
+        self.out.extend(std::iter::repeat_n(' ', indent));
+        self.col = indent;
+    }
+
+    fn node(&mut self, node: &Node, align_pairs: bool) {
+        match node {
+            Node::Whitespace(_) | Node::Comma(_) => {}
+            Node::Comment(text) => self.write(text.trim_end()),
+            Node::Atom(text) => self.write(text),
+            Node::Prefix { prefix, children } => self.prefix(prefix, children),
+            Node::Collection {
+                open,
+                children,
+                close,
+            } => self.collection(open, children, close, align_pairs),
+        }
+    }
+
+    fn prefix(&mut self, prefix: &str, children: &[Node]) {
+        let start = self.col;
+        self.write(prefix);
+        let items = items(children);
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 && (item.breaks > 0 || items[i - 1].is_comment()) {
+                self.newline(item.breaks.clamp(1, MAX_BREAKS), start);
+            } else if i > 0 || item.is_comment() {
+                self.write(" ");
+            }
+            self.node(item.node, false);
+        }
+    }
+
+    fn collection(&mut self, open: &str, children: &[Node], close: &str, align_pairs: bool) {
+        let start = self.col;
+        self.write(open);
+
+        let items = items(children);
+        let head = match items.first().map(|item| item.node) {
+            Some(Node::Atom(head)) if open.ends_with('(') => Some(head.as_ref()),
+            _ => None,
+        };
+
+        let mut indent = match head {
+            Some(head) if BODY_FORMS.contains(&head) => start + open.len() + 1,
+            _ => start + open.len(),
+        };
+        let key_width = if align_pairs || open == "{" {
+            pair_key_width(&items)
+        } else {
+            None
+        };
+
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                if item.breaks > 0 || items[i - 1].is_comment() {
+                    self.newline(item.breaks.clamp(1, MAX_BREAKS), indent);
+                } else {
+                    let padding = match (key_width, &items[i - 1].node) {
+                        (Some(width), Node::Atom(key)) if i % 2 == 1 => {
+                            width - key.chars().count() + 1
+                        }
+                        _ => 1,
+                    };
+                    self.write(&" ".repeat(padding));
+                    // Arguments that start on the head's line set the alignment for the rest.
+                    if i == 1 && head.is_some_and(|head| !BODY_FORMS.contains(&head)) {
+                        indent = self.col;
+                    }
+                }
+            }
+
+            let bindings = i == 1
+                && head.is_some_and(|head| BINDING_FORMS.contains(&head))
+                && matches!(item.node, Node::Collection { open, .. } if open.as_ref() == "[");
+            self.node(item.node, bindings);
+            if item.comma {
+                self.write(",");
+            }
+        }
+
+        if items.last().is_some_and(Item::is_comment) {
+            self.newline(1, indent);
+        }
+        self.write(close);
+    }
+}
+
+// Keys and values are aligned into columns only when every pair sits on its own line and every
+// key is a plain atom.
+fn pair_key_width(items: &[Item]) -> Option<usize> {
+    if items.len() < 4 || items.len() % 2 == 1 || items.iter().any(Item::is_comment) {
+        return None;
+    }
+
+    let mut width = 0;
+    for (i, pair) in items.chunks(2).enumerate() {
+        let [key, value] = pair else {
+            return None;
+        };
+        let Node::Atom(text) = key.node else {
+            return None;
+        };
+        if (i > 0 && key.breaks == 0) || value.breaks > 0 || key.comma {
+            return None;
+        }
+        width = width.max(text.chars().count());
+    }
+
+    Some(width)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn formatting_is_idempotent_on_the_test_suite() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../tests");
+        let mut formatted = 0;
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "mal") {
+                continue;
+            }
+            let src = fs::read_to_string(&path).unwrap();
+            // Some test files deliberately contain unbalanced input.
+            let Ok(once) = format(&src) else {
+                continue;
+            };
+            assert_eq!(format(&once).unwrap(), once, "{}", path.display());
+            formatted += 1;
+        }
+        assert!(formatted > 0);
+    }
+
+    #[test]
+    fn keeps_comments_and_at_most_one_blank_line() {
+        let src = "(def! a 1) ; one\n\n\n\n(def! b 2)";
+        assert_eq!(format(src).unwrap(), "(def! a 1) ; one\n\n(def! b 2)\n");
+    }
+
+    #[test]
+    fn empty_source_stays_empty() {
+        assert_eq!(format("").unwrap(), "");
+        assert_eq!(format(" \n\n ").unwrap(), "");
+    }
+}
@@ -0,0 +1,85 @@
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::process::ExitCode;
+
+mod format;
+
+const USAGE: &str = "\
+Usage: mal-fmt [--check] [FILES...]
+
+Formats each FILE in place, or stdin to stdout when no FILES are given.
+
+Options:
+  --check       Report files that are not formatted instead of rewriting them
+                and exit with status 1 if there are any.
+  -h, --help    Show this message.
+";
+
+fn main() -> ExitCode {
+    let mut check = false;
+    let mut files = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--check" => check = true,
+            "-h" | "--help" => {
+                print!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            other if other.starts_with('-') && other != "-" => {
+                eprintln!("mal-fmt: unknown option {other}\n\n{USAGE}");
+                return ExitCode::from(2);
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    if files.is_empty() {
+        files.push("-".into());
+    }
+
+    let mut unformatted = false;
+    for file in &files {
+        let src = if file == "-" {
+            let mut src = String::new();
+            io::stdin().read_to_string(&mut src).map(|_| src)
+        } else {
+            fs::read_to_string(file)
+        };
+        let src = match src {
+            Ok(src) => src,
+            Err(e) => {
+                eprintln!("mal-fmt: {file}: {e}");
+                return ExitCode::from(2);
+            }
+        };
+
+        let formatted = match format::format(&src) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("mal-fmt: {file}: {e}");
+                return ExitCode::from(2);
+            }
+        };
+
+        if check {
+            if formatted != src {
+                println!("{file}");
+                unformatted = true;
+            }
+        } else if file == "-" {
+            print!("{formatted}");
+        } else if formatted != src
+            && let Err(e) = fs::write(file, formatted)
+        {
+            eprintln!("mal-fmt: {file}: {e}");
+            return ExitCode::from(2);
+        }
+    }
+
+    if unformatted {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
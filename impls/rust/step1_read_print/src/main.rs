@@ -1,47 +1,54 @@
 use std::io::{self, Write};
 
-use common::parser::{self, errors::ReplError};
+use common::{
+    parser::{self, errors::ReplError},
+    repl,
+};
 
 fn main() {
-    let stdin = io::stdin();
     let mut stdout = io::stdout();
-    let mut buffer = String::new();
+    parser::set_print_width(repl::width());
 
     loop {
-        if stdout.write_all(b"user> ").is_err() {
-            continue;
-        }
-
-        while stdout.flush().is_err() {}
-
-        if let Ok(read) = stdin.read_line(&mut buffer) {
-            if read == 0 {
+        let line = match repl::readline("user> ") {
+            Ok(Some(line)) => line,
+            Ok(None) => {
                 let _ = stdout.write_all(b"EOF\n");
                 let _ = stdout.flush();
                 break;
             }
+            Err(e) => {
+                let _ = stdout.write_all(e.to_string().as_bytes());
+                let _ = stdout.write_all(b"\n");
+                let _ = stdout.flush();
+                break;
+            }
+        };
 
-            match parser::rep(&buffer) {
-                Ok(parsed) => {
-                    let _ = stdout.write_all(parsed.as_bytes());
+        match repl::command(&line) {
+            Some(repl::Command::Quit) => break,
+            Some(repl::Command::Continue) => continue,
+            None => {}
+        }
+
+        match parser::rep_with_history(&line) {
+            Ok(parsed) => {
+                let _ = stdout.write_all(repl::colorize(&parsed).as_bytes());
+                let _ = stdout.flush();
+            }
+            Err(e) => match e {
+                ReplError::Unclosed(_) => {
+                    let _ = stdout.write_all(b"unbalanced\n");
                     let _ = stdout.flush();
                 }
-                Err(e) => match e {
-                    ReplError::Unclosed(_) => {
-                        let _ = stdout.write_all(b"unbalanced\n");
-                        let _ = stdout.flush();
-                    }
-                    ReplError::Eof => {
-                        break;
-                    }
-                    _ => {
-                        let _ = stdout.write_all(e.to_string().as_bytes());
-                        let _ = stdout.write_all(b"\n");
-                        let _ = stdout.flush();
-                    }
-                },
-            }
-            buffer.clear();
+                // A blank or comment-only line has nothing to evaluate.
+                ReplError::Eof => {}
+                _ => {
+                    let _ = stdout.write_all(e.to_string().as_bytes());
+                    let _ = stdout.write_all(b"\n");
+                    let _ = stdout.flush();
+                }
+            },
         }
     }
 }
@@ -0,0 +1,77 @@
+pub const USAGE: &str = "\
+Usage: mal [OPTIONS] [SCRIPT | -] [ARGS...]
+
+Options:
+  -e EXPR         Evaluate EXPR and print the result. May be repeated.
+  -i              Start the REPL after running the script or expressions.
+  --no-prelude    Skip loading the prelude.
+  --bundle OUT    Write SCRIPT and the files it loads into the executable OUT.
+  --max-depth N   Limit how deeply forms and values may nest. Raising it past
+                  the default can overflow the stack.
+  -h, --help      Show this message.
+
+SCRIPT is evaluated with the remaining ARGS bound to *ARGV*. Use - to read
+the program from stdin. Without a script or -e the REPL is started.
+";
+
+pub enum Script {
+    File(String),
+    Stdin,
+}
+
+#[derive(Default)]
+pub struct Options {
+    pub exprs: Vec<String>,
+    pub script: Option<Script>,
+    pub args: Vec<String>,
+    pub interactive: bool,
+    pub prelude: bool,
+    pub bundle: Option<String>,
+    pub max_depth: Option<usize>,
+    pub help: bool,
+}
+
+pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        prelude: true,
+        ..Default::default()
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-e" => match args.next() {
+                Some(expr) => options.exprs.push(expr),
+                None => return Err("-e requires an expression".into()),
+            },
+            "-i" => options.interactive = true,
+            "--no-prelude" => options.prelude = false,
+            "--bundle" => match args.next() {
+                Some(output) => options.bundle = Some(output),
+                None => return Err("--bundle requires an output path".into()),
+            },
+            "--max-depth" => match args.next().and_then(|depth| depth.parse().ok()) {
+                Some(depth) if depth > 0 => options.max_depth = Some(depth),
+                _ => return Err("--max-depth requires a positive number".into()),
+            },
+            "-h" | "--help" => options.help = true,
+            "--" => {
+                if let Some(script) = args.next() {
+                    options.script = Some(Script::File(script));
+                }
+                break;
+            }
+            "-" => {
+                options.script = Some(Script::Stdin);
+                break;
+            }
+            other if other.starts_with('-') => return Err(format!("unknown option {other}")),
+            _ => {
+                options.script = Some(Script::File(arg));
+                break;
+            }
+        }
+    }
+
+    options.args = args.collect();
+    Ok(options)
+}
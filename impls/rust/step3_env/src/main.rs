@@ -1,47 +1,161 @@
-use std::io::{self, Write};
+use std::env;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
 
-use common::parser::{self, errors::ReplError};
+use common::{
+    bundle::Bundle,
+    parser::{self, errors::ReplError},
+    repl,
+};
 
-fn main() {
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
-    let mut buffer = String::new();
+use crate::cli::Script;
 
-    loop {
-        if stdout.write_all(b"user> ").is_err() {
-            continue;
+mod cli;
+
+fn main() -> ExitCode {
+    match Bundle::embedded() {
+        Ok(Some(bundle)) => return run_bundle(bundle),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("mal: corrupt bundle: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("mal: {e}\n\n{}", cli::USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    if options.help {
+        print!("{}", cli::USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    if let Some(output) = &options.bundle {
+        let Some(Script::File(script)) = &options.script else {
+            eprintln!("mal: --bundle requires a script file\n\n{}", cli::USAGE);
+            return ExitCode::from(2);
+        };
+        let res = Bundle::collect(script)
+            .and_then(|bundle| bundle.write(Path::new(output)).map_err(ReplError::from));
+        if let Err(e) = res {
+            eprintln!("mal: bundle: {e}");
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    if let Some(depth) = options.max_depth {
+        parser::set_max_depth(depth);
+    }
+
+    if options.prelude
+        && let Err(e) = parser::load_prelude()
+    {
+        eprintln!("mal: prelude: {e}");
+        return ExitCode::FAILURE;
+    }
+    parser::set_argv(&options.args);
+
+    for expr in &options.exprs {
+        match parser::eval_string(expr) {
+            Ok(res) => print!("{res}"),
+            Err(e) => {
+                eprintln!("mal: {e}");
+                return ExitCode::FAILURE;
+            }
         }
+    }
 
-        while stdout.flush().is_err() {}
+    let res = match &options.script {
+        Some(Script::File(path)) => parser::load_file(path),
+        Some(Script::Stdin) => {
+            let mut src = String::new();
+            io::stdin()
+                .read_to_string(&mut src)
+                .map_err(ReplError::from)
+                .and_then(|_| parser::eval_string(&src).map(|_| ()))
+        }
+        None => Ok(()),
+    };
+    if let Err(e) = res {
+        eprintln!("mal: {e}");
+        return ExitCode::FAILURE;
+    }
 
-        if let Ok(read) = stdin.read_line(&mut buffer) {
-            if read == 0 {
+    if options.interactive || (options.exprs.is_empty() && options.script.is_none()) {
+        run_repl();
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn run_bundle(bundle: Bundle) -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    bundle.register();
+
+    let res = parser::load_prelude().and_then(|_| {
+        parser::set_argv(&args);
+        parser::load_file(&bundle.main)
+    });
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("mal: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_repl() {
+    let mut stdout = io::stdout();
+    parser::set_print_width(repl::width());
+
+    loop {
+        let line = match repl::readline("user> ") {
+            Ok(Some(line)) => line,
+            Ok(None) => {
                 let _ = stdout.write_all(b"EOF\n");
                 let _ = stdout.flush();
                 break;
             }
+            Err(e) => {
+                let _ = stdout.write_all(e.to_string().as_bytes());
+                let _ = stdout.write_all(b"\n");
+                let _ = stdout.flush();
+                break;
+            }
+        };
+
+        match repl::command(&line) {
+            Some(repl::Command::Quit) => break,
+            Some(repl::Command::Continue) => continue,
+            None => {}
+        }
 
-            match parser::rep(&buffer) {
-                Ok(parsed) => {
-                    let _ = stdout.write_all(parsed.as_bytes());
+        match parser::rep_with_history(&line) {
+            Ok(parsed) => {
+                let _ = stdout.write_all(repl::colorize(&parsed).as_bytes());
+                let _ = stdout.flush();
+            }
+            Err(e) => match e {
+                ReplError::Unclosed(_) => {
+                    let _ = stdout.write_all(b"unbalanced\n");
                     let _ = stdout.flush();
                 }
-                Err(e) => match e {
-                    ReplError::Unclosed(_) => {
-                        let _ = stdout.write_all(b"unbalanced\n");
-                        let _ = stdout.flush();
-                    }
-                    ReplError::Eof => {
-                        break;
-                    }
-                    _ => {
-                        let _ = stdout.write_all(e.to_string().as_bytes());
-                        let _ = stdout.write_all(b"\n");
-                        let _ = stdout.flush();
-                    }
-                },
-            }
-            buffer.clear();
+                // A blank or comment-only line has nothing to evaluate.
+                ReplError::Eof => {}
+                _ => {
+                    let _ = stdout.write_all(e.to_string().as_bytes());
+                    let _ = stdout.write_all(b"\n");
+                    let _ = stdout.flush();
+                }
+            },
         }
     }
 }